@@ -0,0 +1,104 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use ::async_io::{Async, Timer};
+use futures_lite::future;
+use socket2::Socket;
+
+use crate::errors::Error;
+use crate::iter::PingIter;
+use crate::ping::{Ping, PingResult};
+use crate::runtime::{self, Runtime};
+use crate::stream::PingStream;
+
+/// The async-io [`Runtime`], driving the socket through [`Async`]. This is the
+/// reactor used by smol.
+pub(crate) struct AsyncIo;
+
+impl Runtime for AsyncIo {
+    type Socket = Async<Socket>;
+
+    fn register(socket: Socket) -> io::Result<Self::Socket> {
+        Async::new(socket)
+    }
+
+    fn get_ref(socket: &Self::Socket) -> &Socket {
+        socket.get_ref()
+    }
+
+    async fn read_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R> {
+        socket.read_with(op).await
+    }
+
+    async fn write_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R> {
+        socket.write_with(op).await
+    }
+
+    async fn timeout<T>(duration: Duration, future: impl Future<Output = T>) -> Option<T> {
+        future::or(async { Some(future.await) }, async {
+            Timer::after(duration).await;
+            None
+        })
+        .await
+    }
+
+    async fn sleep(duration: Duration) {
+        Timer::after(duration).await;
+    }
+
+    fn iter_socket<'i>(iter: &'i mut PingIter<'_>) -> &'i mut Option<Self::Socket> {
+        &mut iter.async_io_socket
+    }
+}
+
+impl Ping<'_> {
+    /// Sends the echo request and waits asynchronously until a matching reply
+    /// arrives or the timeout elapses, using the async-io reactor (as used by
+    /// smol).
+    ///
+    /// Behaves exactly like [`send`](Ping::send), and like `send_async` is
+    /// cancel-safe: dropping the future closes its socket. It can be awaited
+    /// from any executor.
+    ///
+    /// Only available with the `async-io` feature.
+    pub async fn send_async_io(&self) -> Result<PingResult, Error> {
+        runtime::send::<AsyncIo>(self).await
+    }
+}
+
+impl<'a> PingIter<'a> {
+    /// Waits for the next request to be due, sends it and waits
+    /// asynchronously for its reply, yielding the same items as
+    /// [`next`](Iterator::next) without blocking.
+    ///
+    /// The requests are sent as with [`Ping::send_async_io`], but share one
+    /// socket, opened by the first call and kept by the iterator, so their
+    /// ICMP identifier stays the same. It is not the socket the blocking
+    /// `next` uses. See [`into_stream_io`](PingIter::into_stream_io) to use the
+    /// iterator as a [`Stream`](futures_core::Stream).
+    ///
+    /// Only available with the `async-io` feature.
+    pub async fn next_async_io(&mut self) -> Option<Result<PingResult, Error>> {
+        runtime::next::<AsyncIo>(self).await
+    }
+
+    /// Turns the iterator into a [`PingStream`] driven by the async-io reactor,
+    /// which yields the items of [`next_async_io`](PingIter::next_async_io).
+    ///
+    /// Only available with the `async-io` feature.
+    pub fn into_stream_io(self) -> PingStream<'a> {
+        PingStream::new(self, |mut iter| {
+            Box::pin(async move {
+                let item = runtime::next::<AsyncIo>(&mut iter).await;
+                (iter, item)
+            })
+        })
+    }
+}
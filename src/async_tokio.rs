@@ -0,0 +1,108 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use socket2::Socket;
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+
+use crate::errors::Error;
+use crate::iter::PingIter;
+use crate::ping::{Ping, PingResult};
+use crate::runtime::{self, Runtime};
+use crate::stream::PingStream;
+
+/// The tokio [`Runtime`], driving the socket through [`AsyncFd`].
+pub(crate) struct Tokio;
+
+impl Runtime for Tokio {
+    type Socket = AsyncFd<Socket>;
+
+    fn register(socket: Socket) -> io::Result<Self::Socket> {
+        socket.set_nonblocking(true)?;
+        // SAFETY: the `AsyncFd` takes ownership of the socket, so its file
+        // descriptor stays open and unchanged until the `AsyncFd` is dropped.
+        unsafe { AsyncFd::register(socket) }.map_err(io::Error::from)
+    }
+
+    fn get_ref(socket: &Self::Socket) -> &Socket {
+        socket.get_ref()
+    }
+
+    async fn read_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R> {
+        // ICMP errors queued with IP_RECVERR only signal an error condition.
+        socket
+            .async_io(Interest::READABLE | Interest::ERROR, op)
+            .await
+    }
+
+    async fn write_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R> {
+        socket.async_io(Interest::WRITABLE, op).await
+    }
+
+    async fn timeout<T>(duration: Duration, future: impl Future<Output = T>) -> Option<T> {
+        tokio::time::timeout(duration, future).await.ok()
+    }
+
+    async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    fn iter_socket<'i>(iter: &'i mut PingIter<'_>) -> &'i mut Option<Self::Socket> {
+        &mut iter.tokio_socket
+    }
+}
+
+impl Ping<'_> {
+    /// Sends the echo request and waits asynchronously until a matching reply
+    /// arrives or the timeout elapses.
+    ///
+    /// This is the non-blocking counterpart of [`send`](Ping::send) and
+    /// reports results and errors the same way. It must be awaited inside a
+    /// tokio runtime with the I/O and time drivers enabled.
+    ///
+    /// The future owns its socket, so it is cancel-safe: dropping it before
+    /// completion closes the socket and any late reply is discarded by the
+    /// kernel.
+    ///
+    /// Only available with the `tokio` feature on Unix platforms.
+    pub async fn send_async(&self) -> Result<PingResult, Error> {
+        runtime::send::<Tokio>(self).await
+    }
+}
+
+impl<'a> PingIter<'a> {
+    /// Waits for the next request to be due, sends it and waits
+    /// asynchronously for its reply, yielding the same items as
+    /// [`next`](Iterator::next) without blocking.
+    ///
+    /// The requests are sent as with [`Ping::send_async`], but share one
+    /// socket, opened by the first call and kept by the iterator, so their
+    /// ICMP identifier stays the same. It is not the socket the blocking
+    /// `next` uses. See [`into_stream`](PingIter::into_stream) to use the
+    /// iterator as a [`Stream`](futures_core::Stream).
+    ///
+    /// Only available with the `tokio` feature on Unix platforms.
+    pub async fn next_async(&mut self) -> Option<Result<PingResult, Error>> {
+        runtime::next::<Tokio>(self).await
+    }
+
+    /// Turns the iterator into a [`PingStream`] driven by tokio, which yields
+    /// the items of [`next_async`](PingIter::next_async).
+    ///
+    /// Only available with the `tokio` feature on Unix platforms.
+    pub fn into_stream(self) -> PingStream<'a> {
+        PingStream::new(self, |mut iter| {
+            Box::pin(async move {
+                let item = runtime::next::<Tokio>(&mut iter).await;
+                (iter, item)
+            })
+        })
+    }
+}
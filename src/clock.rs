@@ -0,0 +1,44 @@
+use std::fmt;
+use std::time::Instant;
+
+/// A source of monotonic time, used to measure round-trip times and to decide
+/// when a ping has timed out.
+///
+/// The default, [`MonotonicClock`], reads [`Instant::now`], which never goes
+/// backwards, unlike the wall clock. Set another clock with [`Ping::clock`],
+/// for example to inject a fake clock in tests.
+///
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::time::{Duration, Instant};
+///
+/// /// A clock that moves 10ms forward every time it is read.
+/// #[derive(Debug)]
+/// struct FakeClock {
+///     start: Instant,
+///     reads: AtomicU32,
+/// }
+///
+/// impl ping::Clock for FakeClock {
+///     fn now(&self) -> Instant {
+///         let reads = self.reads.fetch_add(1, Ordering::Relaxed);
+///         self.start + Duration::from_millis(10) * reads
+///     }
+/// }
+/// ```
+///
+/// [`Ping::clock`]: crate::Ping::clock
+pub trait Clock: fmt::Debug + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The default [`Clock`], backed by [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use thiserror::Error;
 
 /// Errors that can occur while sending a ping or decoding its reply.
@@ -12,6 +14,29 @@ pub enum Error {
     /// The ICMP echo reply could not be decoded.
     #[error("Decode echo reply error occurred while processing the ICMP echo reply.")]
     DecodeEchoReplyError,
+    /// A router or the target reported that the target cannot be reached.
+    /// `code` is the ICMP code, whose meaning differs between ICMPv4 and
+    /// ICMPv6 (e.g. host unreachable is 1 and 3 respectively).
+    #[error("destination unreachable (code {code}) reported by {reporter}")]
+    DestinationUnreachable { reporter: IpAddr, code: u8 },
+    /// The request was larger than the MTU of a link on the path and could
+    /// not be fragmented. Reported by ICMPv6 Packet Too Big and by ICMPv4
+    /// Fragmentation Needed; `mtu` is the next-hop MTU, which old routers
+    /// report as 0.
+    #[error("packet too big for MTU {mtu} reported by {reporter}")]
+    PacketTooBig { reporter: IpAddr, mtu: u32 },
+    /// The request's TTL or hop limit ran out at `reporter` (code 0), or the
+    /// target timed out reassembling it (code 1).
+    #[error("time exceeded (code {code}) reported by {reporter}")]
+    TimeExceeded { reporter: IpAddr, code: u8 },
+    /// `reporter` found a problem in the request's IP header.
+    #[error("parameter problem (code {code}) reported by {reporter}")]
+    ParameterProblem { reporter: IpAddr, code: u8 },
+    /// The process may not set the socket mark requested with `Ping::mark`,
+    /// which needs the `CAP_NET_ADMIN` capability on Linux, or `CAP_NET_RAW`
+    /// since Linux 5.17.
+    #[error("setting the socket mark requires CAP_NET_ADMIN or CAP_NET_RAW")]
+    MarkNotPermitted,
     /// An underlying I/O error. A timeout is reported here with kind
     /// [`ErrorKind::TimedOut`](std::io::ErrorKind::TimedOut).
     #[error("io error: {error}")]
@@ -0,0 +1,115 @@
+use std::time::{Duration, Instant};
+
+use crate::errors::Error;
+use crate::ping::{Ping, PingResult};
+use crate::pinger::Pinger;
+
+pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An iterator over a series of pings, like the `ping` command line tool.
+///
+/// Create one with [`Ping::iter`]. It yields one result per request, with
+/// sequence numbers counting up from the builder's
+/// [`seq_cnt`](Ping::seq_cnt), and stops after [`count`](Ping::count) requests
+/// or at the [`deadline`](Ping::deadline). Requests are spaced by the
+/// [`interval`](Ping::interval). A request that gets no reply in time yields
+/// the timeout error and the iteration carries on.
+///
+/// All requests share one socket, as in a [`Pinger`] session.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let target = "8.8.8.8".parse().unwrap();
+/// for result in ping::new(target).count(4).interval(Duration::from_millis(500)).iter() {
+///     match result {
+///         Ok(result) => println!("seq={} time={:?}", result.seq_cnt, result.rtt),
+///         Err(e) => println!("{e}"),
+///     }
+/// }
+/// ```
+///
+/// With the `tokio` or `async-io` feature, the same iterator can be driven
+/// asynchronously with `next_async` or `next_async_io`, or turned into a
+/// `PingStream` with `into_stream` or `into_stream_io`.
+#[derive(Debug)]
+pub struct PingIter<'a> {
+    pub(crate) pinger: Pinger<'a>,
+    /// The socket of `next_async`, registered with tokio.
+    #[cfg(all(feature = "tokio", unix))]
+    pub(crate) tokio_socket: Option<tokio::io::unix::AsyncFd<socket2::Socket>>,
+    /// The socket of `next_async_io`, registered with async-io.
+    #[cfg(feature = "async-io")]
+    pub(crate) async_io_socket: Option<::async_io::Async<socket2::Socket>>,
+    count: Option<usize>,
+    interval: Duration,
+    deadline: Option<Duration>,
+    sent: usize,
+    time_start: Option<Instant>,
+    last_sent: Option<Instant>,
+}
+
+impl<'a> PingIter<'a> {
+    fn new(ping: &Ping<'a>) -> Self {
+        PingIter {
+            pinger: ping.pinger(),
+            #[cfg(all(feature = "tokio", unix))]
+            tokio_socket: None,
+            #[cfg(feature = "async-io")]
+            async_io_socket: None,
+            count: ping.count,
+            interval: ping.interval.unwrap_or(DEFAULT_INTERVAL),
+            deadline: ping.deadline,
+            sent: 0,
+            time_start: None,
+            last_sent: None,
+        }
+    }
+
+    /// Schedules the next request, returning how long to wait before sending
+    /// it and how long to wait for its reply, or `None` once the iteration is
+    /// over.
+    pub(crate) fn schedule(&mut self) -> Option<(Duration, Duration)> {
+        if self.count.is_some_and(|count| self.sent >= count) {
+            return None;
+        }
+
+        let now = self.pinger.ping.clock_or_default().now();
+        let time_start = *self.time_start.get_or_insert(now);
+        let send_at = match self.last_sent {
+            Some(last_sent) => (last_sent + self.interval).max(now),
+            None => now,
+        };
+
+        let mut timeout = self.pinger.ping.timeout_or_default();
+        if let Some(deadline) = self.deadline {
+            let end = time_start + deadline;
+            if send_at >= end {
+                return None;
+            }
+            timeout = timeout.min(end - send_at);
+        }
+
+        self.sent += 1;
+        self.last_sent = Some(send_at);
+        Some((send_at - now, timeout))
+    }
+}
+
+impl Iterator for PingIter<'_> {
+    type Item = Result<PingResult, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (wait, timeout) = self.schedule()?;
+        std::thread::sleep(wait);
+        Some(self.pinger.send_timeout(timeout))
+    }
+}
+
+impl<'a> Ping<'a> {
+    /// Returns an iterator that keeps pinging the target, like the `ping`
+    /// command line tool. See [`PingIter`].
+    pub fn iter(&self) -> PingIter<'a> {
+        PingIter::new(self)
+    }
+}
@@ -22,6 +22,24 @@
 //! println!("round-trip time: {:?}", result.rtt);
 //! ```
 //!
+//! # Sessions
+//!
+//! To send a series of requests, start a [`Pinger`] with [`Ping::pinger`]. It
+//! keeps its sockets open and numbers the requests itself. To ping many hosts
+//! at once, use [`Pinger::send_many`]. For `ping -c N -i X -w D` style runs,
+//! set [`Ping::count`], [`Ping::interval`] and [`Ping::deadline`] and iterate
+//! over [`Ping::iter`]. Feed the results to [`PingStatistics`] for the usual
+//! loss and round-trip time summary.
+//!
+//! # Traceroute
+//!
+//! [`Ping::traceroute`] walks the path to the target with TTL-limited
+//! requests and yields the responding router of every [`Hop`]. An ICMP error
+//! reported in place of a reply, such as a router's Time Exceeded, is
+//! returned as the matching [`Error`] variant. To keep watching every hop of
+//! the path, with loss and round-trip statistics per hop, use [`Ping::mtr`].
+//! [`Ping::path_mtu`] finds the largest packet the path carries unfragmented.
+//!
 //! # Pinging a host name
 //!
 //! Only an [`IpAddr`] is accepted. To ping a host name, resolve it first with
@@ -47,13 +65,47 @@
 //! a [`DGRAM`] socket works unprivileged on most systems. See [`SocketType`]
 //! for the per-platform default and how to override it.
 //!
+//! # Async
+//!
+//! With the `tokio` cargo feature enabled, `Ping::send_async` sends the
+//! request without blocking the calling thread, for use inside a tokio runtime.
+//! The `async-io` feature adds `Ping::send_async_io`, which runs on the
+//! async-io reactor used by smol. Both behave exactly like [`Ping::send`].
+//! A [`PingIter`] turns into a `PingStream` of results with
+//! `PingIter::into_stream` or `PingIter::into_stream_io`.
+//!
 //! [`IpAddr`]: std::net::IpAddr
 
+#[cfg(feature = "async-io")]
+mod async_io;
+#[cfg(all(feature = "tokio", unix))]
+mod async_tokio;
+mod clock;
 mod errors;
+mod iter;
+mod mtr;
 mod packet;
 mod ping;
+mod pinger;
+mod pmtu;
+#[cfg(any(all(feature = "tokio", unix), feature = "async-io"))]
+mod runtime;
+mod stats;
+#[cfg(any(all(feature = "tokio", unix), feature = "async-io"))]
+mod stream;
+mod sys;
+mod traceroute;
 
+pub use crate::clock::{Clock, MonotonicClock};
 pub use crate::errors::Error;
+pub use crate::iter::PingIter;
+pub use crate::mtr::{Mtr, MtrHop, MtrReport};
 pub use crate::ping::{
-    Ping, PingResult, SocketType, SocketType::DGRAM, SocketType::RAW, dgramsock, new, ping, rawsock,
+    IpOption, IpTimestamp, PayloadPattern, Ping, PingResult, SocketType, SocketType::DGRAM,
+    SocketType::RAW, TimestampSource, dgramsock, new, ping, rawsock,
 };
+pub use crate::pinger::Pinger;
+pub use crate::stats::PingStatistics;
+#[cfg(any(all(feature = "tokio", unix), feature = "async-io"))]
+pub use crate::stream::PingStream;
+pub use crate::traceroute::{Hop, Probe, Traceroute};
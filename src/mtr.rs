@@ -0,0 +1,207 @@
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::errors::Error;
+use crate::iter::DEFAULT_INTERVAL;
+use crate::ping::Ping;
+use crate::pinger::Pinger;
+use crate::stats::PingStatistics;
+use crate::traceroute::{DEFAULT_MAX_HOPS, Probe, is_last_hop, probe};
+
+/// A continuous monitor of every hop on the path to the target, like the
+/// `mtr` command line tool.
+///
+/// Create one with [`Ping::mtr`]. Every round probes the path hop by hop with
+/// TTL-limited echo requests, as a [`Traceroute`](crate::Traceroute) with one
+/// probe per hop does, and then yields an [`MtrReport`] of everything seen so
+/// far. Rounds are spaced by the builder's [`interval`](Ping::interval) and
+/// there are [`count`](Ping::count) of them, or no end when it is unset.
+/// Each probe waits for its answer up to the builder's
+/// [`timeout`](Ping::timeout), and the socket is set up from the builder as
+/// for any other ping.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let target = "8.8.8.8".parse().unwrap();
+/// let report = ping::new(target)
+///     .timeout(Duration::from_secs(1))
+///     .count(10)
+///     .mtr()
+///     .last()
+///     .unwrap();
+/// for hop in &report.hops {
+///     println!("{:2} {:?} {}", hop.ttl, hop.last_addr, hop.statistics);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Mtr<'a> {
+    pinger: Pinger<'a>,
+    max_hops: u32,
+    count: Option<usize>,
+    interval: Duration,
+    last_round: Option<Instant>,
+    report: MtrReport,
+    path_len: Option<u32>,
+}
+
+/// A snapshot of the per-hop statistics gathered by an [`Mtr`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MtrReport {
+    /// The target of the path.
+    pub target: IpAddr,
+    /// How many rounds of probes were sent.
+    pub rounds: usize,
+    /// The hops of the path, starting at the first. The last one is the
+    /// target once it has answered.
+    pub hops: Vec<MtrHop>,
+}
+
+/// What was seen at one hop of the path monitored by an [`Mtr`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct MtrHop {
+    /// The TTL probes of this hop are sent with; the first hop is 1.
+    pub ttl: u32,
+    /// Loss and round-trip times of the probes of this hop. Probes that went
+    /// unanswered or failed count as lost.
+    pub statistics: PingStatistics,
+    /// Every address that answered at this hop, with a reply or an ICMP
+    /// error, with how many times it did, in the order they were first seen.
+    /// More than one usually means the traffic is balanced over several paths
+    /// (ECMP).
+    pub addrs: Vec<(IpAddr, usize)>,
+    /// The address of the most recent answer.
+    pub last_addr: Option<IpAddr>,
+}
+
+impl MtrHop {
+    fn new(ttl: u32) -> Self {
+        MtrHop {
+            ttl,
+            statistics: PingStatistics::new(),
+            addrs: Vec::new(),
+            last_addr: None,
+        }
+    }
+
+    fn record(&mut self, probe: &Result<Probe, Error>) {
+        let addr = match probe {
+            Ok(probe) => {
+                self.statistics.record_rtt(probe.rtt);
+                probe.addr
+            }
+            // An ICMP error, such as Destination Unreachable, still tells who
+            // answered at this hop.
+            Err(
+                Error::DestinationUnreachable { reporter, .. }
+                | Error::PacketTooBig { reporter, .. }
+                | Error::TimeExceeded { reporter, .. }
+                | Error::ParameterProblem { reporter, .. },
+            ) => {
+                self.statistics.record_loss();
+                *reporter
+            }
+            Err(_) => {
+                self.statistics.record_loss();
+                return;
+            }
+        };
+        match self.addrs.iter_mut().find(|(known, _)| *known == addr) {
+            Some((_, answers)) => *answers += 1,
+            None => self.addrs.push((addr, 1)),
+        }
+        self.last_addr = Some(addr);
+    }
+}
+
+#[allow(clippy::needless_return)]
+impl<'a> Mtr<'a> {
+    fn new(ping: &Ping<'a>) -> Self {
+        Mtr {
+            pinger: ping.pinger(),
+            max_hops: DEFAULT_MAX_HOPS,
+            count: ping.count,
+            interval: ping.interval.unwrap_or(DEFAULT_INTERVAL),
+            last_round: None,
+            report: MtrReport {
+                target: ping.addr,
+                rounds: 0,
+                hops: Vec::new(),
+            },
+            path_len: None,
+        }
+    }
+
+    /// Sets the largest TTL to probe with. Defaults to 30.
+    pub fn max_hops(&mut self, max_hops: u32) -> &mut Self {
+        self.max_hops = max_hops;
+        return self;
+    }
+
+    /// The statistics gathered so far.
+    pub fn report(&self) -> &MtrReport {
+        &self.report
+    }
+
+    /// Probes every hop once, up to the target or the end of the path found
+    /// so far.
+    fn round(&mut self) {
+        self.report.rounds += 1;
+        for ttl in 1..=self.max_hops {
+            let probe = match self.pinger.set_ttl(ttl) {
+                Ok(()) => probe(&mut self.pinger),
+                Err(error) => Err(error),
+            };
+
+            let index = ttl as usize - 1;
+            if self.report.hops.len() == index {
+                self.report.hops.push(MtrHop::new(ttl));
+            }
+            self.report.hops[index].record(&probe);
+
+            if is_last_hop(&probe) {
+                // The path may have become shorter since the last round.
+                self.report.hops.truncate(ttl as usize);
+                self.path_len = Some(ttl);
+                break;
+            }
+            // A silent hop at the end of the path found earlier does not make
+            // it any longer.
+            if probe.is_err() && self.path_len.is_some_and(|path_len| ttl >= path_len) {
+                break;
+            }
+        }
+    }
+}
+
+impl Iterator for Mtr<'_> {
+    type Item = MtrReport;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count.is_some_and(|count| self.report.rounds >= count) {
+            return None;
+        }
+
+        let clock = self.pinger.ping.clock_or_default();
+        let now = clock.now();
+        let start_at = match self.last_round {
+            Some(last_round) => (last_round + self.interval).max(now),
+            None => now,
+        };
+        std::thread::sleep(start_at - now);
+        self.last_round = Some(start_at);
+
+        self.round();
+        Some(self.report.clone())
+    }
+}
+
+impl<'a> Ping<'a> {
+    /// Returns an iterator that keeps probing every hop on the path to the
+    /// target, like the `mtr` command line tool. See [`Mtr`].
+    pub fn mtr(&self) -> Mtr<'a> {
+        Mtr::new(self)
+    }
+}
@@ -1,9 +1,9 @@
 use std::io::Write;
 use thiserror::Error;
 
+use super::checksum;
+
 pub const HEADER_SIZE: usize = 8;
-/// Size of the correlation token carried as the echo payload.
-pub const PAYLOAD_SIZE: usize = 24;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -16,11 +16,25 @@ pub enum Error {
 pub struct IcmpV4;
 pub struct IcmpV6;
 
+/// The ICMP error messages that can be sent back in place of an echo reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    DestinationUnreachable,
+    /// ICMPv6 Packet Too Big, or ICMPv4 Destination Unreachable with code 4
+    /// (fragmentation needed and DF set).
+    PacketTooBig,
+    TimeExceeded,
+    ParameterProblem,
+}
+
 pub trait Proto {
     const ECHO_REQUEST_TYPE: u8;
     const ECHO_REQUEST_CODE: u8;
     const ECHO_REPLY_TYPE: u8;
     const ECHO_REPLY_CODE: u8;
+
+    fn error_kind(type_: u8, code: u8) -> Option<ErrorKind>;
+    fn mtu(header: &[u8]) -> u32;
 }
 
 impl Proto for IcmpV4 {
@@ -28,6 +42,21 @@ impl Proto for IcmpV4 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 0;
     const ECHO_REPLY_CODE: u8 = 0;
+
+    fn error_kind(type_: u8, code: u8) -> Option<ErrorKind> {
+        match (type_, code) {
+            (3, 4) => Some(ErrorKind::PacketTooBig),
+            (3, _) => Some(ErrorKind::DestinationUnreachable),
+            (11, _) => Some(ErrorKind::TimeExceeded),
+            (12, _) => Some(ErrorKind::ParameterProblem),
+            _ => None,
+        }
+    }
+
+    // RFC 1191: the next-hop MTU is in the low 16 bits.
+    fn mtu(header: &[u8]) -> u32 {
+        u32::from(u16::from_be_bytes([header[6], header[7]]))
+    }
 }
 
 impl Proto for IcmpV6 {
@@ -35,6 +64,20 @@ impl Proto for IcmpV6 {
     const ECHO_REQUEST_CODE: u8 = 0;
     const ECHO_REPLY_TYPE: u8 = 129;
     const ECHO_REPLY_CODE: u8 = 0;
+
+    fn error_kind(type_: u8, _code: u8) -> Option<ErrorKind> {
+        match type_ {
+            1 => Some(ErrorKind::DestinationUnreachable),
+            2 => Some(ErrorKind::PacketTooBig),
+            3 => Some(ErrorKind::TimeExceeded),
+            4 => Some(ErrorKind::ParameterProblem),
+            _ => None,
+        }
+    }
+
+    fn mtu(header: &[u8]) -> u32 {
+        u32::from_be_bytes([header[4], header[5], header[6], header[7]])
+    }
 }
 
 pub struct EchoRequest<'a> {
@@ -53,13 +96,31 @@ impl<'a> EchoRequest<'a> {
         buffer[6] = (self.seq_cnt >> 8) as u8;
         buffer[7] = self.seq_cnt as u8;
 
-        if let Err(_) = (&mut buffer[8..]).write(self.payload) {
+        if (&mut buffer[8..]).write(self.payload).is_err() {
             return Err(Error::InvalidSize);
         }
 
         write_checksum(buffer);
         Ok(())
     }
+
+    /// Decodes an echo request, such as the one quoted in an ICMP error
+    /// message. The payload may be truncated.
+    pub fn decode<P: Proto>(buffer: &'a [u8]) -> Result<Self, Error> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(Error::InvalidSize);
+        }
+
+        if buffer[0] != P::ECHO_REQUEST_TYPE || buffer[1] != P::ECHO_REQUEST_CODE {
+            return Err(Error::InvalidPacket);
+        }
+
+        Ok(EchoRequest {
+            ident: u16::from_be_bytes([buffer[4], buffer[5]]),
+            seq_cnt: u16::from_be_bytes([buffer[6], buffer[7]]),
+            payload: &buffer[HEADER_SIZE..],
+        })
+    }
 }
 
 pub struct EchoReply<'a> {
@@ -70,7 +131,7 @@ pub struct EchoReply<'a> {
 
 impl<'a> EchoReply<'a> {
     pub fn decode<P: Proto>(buffer: &'a [u8]) -> Result<Self, Error> {
-        if buffer.as_ref().len() < HEADER_SIZE + PAYLOAD_SIZE {
+        if buffer.as_ref().len() < HEADER_SIZE {
             return Err(Error::InvalidSize);
         }
 
@@ -82,7 +143,7 @@ impl<'a> EchoReply<'a> {
 
         let ident = (u16::from(buffer[4]) << 8) + u16::from(buffer[5]);
         let seq_cnt = (u16::from(buffer[6]) << 8) + u16::from(buffer[7]);
-        let payload = &buffer[HEADER_SIZE..(HEADER_SIZE + PAYLOAD_SIZE)];
+        let payload = &buffer[HEADER_SIZE..];
 
         Ok(EchoReply {
             ident,
@@ -92,21 +153,52 @@ impl<'a> EchoReply<'a> {
     }
 }
 
-fn write_checksum(buffer: &mut [u8]) {
-    let mut sum = 0u32;
-    for word in buffer.chunks(2) {
-        let mut part = u16::from(word[0]) << 8;
-        if word.len() > 1 {
-            part += u16::from(word[1]);
+/// An ICMP error message, quoting the start of the packet that caused it.
+pub struct ErrorMessage<'a> {
+    pub kind: ErrorKind,
+    pub code: u8,
+    /// The MTU of the next hop, for [`ErrorKind::PacketTooBig`].
+    pub mtu: Option<u32>,
+    /// The IP header and the start of the data of the offending packet.
+    pub original: &'a [u8],
+}
+
+impl<'a> ErrorMessage<'a> {
+    pub fn decode<P: Proto>(buffer: &'a [u8]) -> Result<Self, Error> {
+        if buffer.len() < HEADER_SIZE {
+            return Err(Error::InvalidSize);
         }
-        sum = sum.wrapping_add(u32::from(part));
-    }
 
-    while (sum >> 16) > 0 {
-        sum = (sum & 0xffff) + (sum >> 16);
+        let code = buffer[1];
+        let kind = match P::error_kind(buffer[0], code) {
+            Some(kind) => kind,
+            None => return Err(Error::InvalidPacket),
+        };
+        let mtu = match kind {
+            ErrorKind::PacketTooBig => Some(P::mtu(buffer)),
+            _ => None,
+        };
+
+        Ok(ErrorMessage {
+            kind,
+            code,
+            mtu,
+            original: &buffer[HEADER_SIZE..],
+        })
     }
+}
+
+/// Whether an ICMPv4 message adds up to its checksum. ICMPv6 checksums also
+/// cover a pseudo-header made of the IP addresses, and are left to the
+/// kernel, which verifies them before handing a message to a socket.
+pub fn is_checksum_valid(buffer: &[u8]) -> bool {
+    checksum(buffer) == 0
+}
 
-    let sum = !sum as u16;
+fn write_checksum(buffer: &mut [u8]) {
+    buffer[2] = 0;
+    buffer[3] = 0;
+    let sum = checksum(buffer);
 
     buffer[2] = (sum >> 8) as u8;
     buffer[3] = (sum & 0xff) as u8;
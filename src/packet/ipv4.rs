@@ -1,5 +1,10 @@
+use std::net::Ipv4Addr;
+
 use thiserror::Error;
 
+use super::checksum;
+use crate::ping::IpOption;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("too small header")]
@@ -8,12 +13,19 @@ pub enum Error {
     InvalidHeaderSize,
     #[error("invalid version")]
     InvalidVersion,
+    #[error("not the first fragment")]
+    NotFirstFragment,
     #[error("unknown protocol")]
     UnknownProtocol,
 }
 
 const MINIMUM_PACKET_SIZE: usize = 20;
 
+const OPTION_END: u8 = 0;
+const OPTION_NO_OPERATION: u8 = 1;
+const OPTION_RECORD_ROUTE: u8 = 7;
+const OPTION_TIMESTAMP: u8 = 68;
+
 #[derive(Debug, PartialEq)]
 pub enum IpV4Protocol {
     Icmp,
@@ -29,14 +41,58 @@ impl IpV4Protocol {
 }
 
 pub struct IpV4Packet<'a> {
+    /// The Differentiated Services Code Point, the upper 6 bits of the former
+    /// type of service byte.
+    pub dscp: u8,
+    /// The Explicit Congestion Notification bits, the lower 2 bits of the
+    /// former type of service byte.
+    pub ecn: u8,
+    /// The length of the whole packet, header included, as announced by the
+    /// header.
     #[allow(unused)]
-    pub protocol: IpV4Protocol,
+    pub total_length: u16,
+    #[allow(unused)]
+    pub identification: u16,
+    #[allow(unused)]
+    pub dont_fragment: bool,
+    #[allow(unused)]
+    pub more_fragments: bool,
+    /// The offset of this fragment's data in the original packet, in bytes.
+    /// Always 0, as later fragments are not decoded.
+    #[allow(unused)]
+    pub fragment_offset: u16,
     pub ttl: u8,
+    #[allow(unused)]
+    pub protocol: IpV4Protocol,
+    /// Whether the header adds up to its checksum. A header quoted in an ICMP
+    /// error may not, if the router rewrote it without updating the checksum.
+    pub checksum_valid: bool,
+    #[allow(unused)]
+    pub source: Ipv4Addr,
+    pub destination: Ipv4Addr,
+    /// The raw options, between the fixed header and the data.
+    pub options: &'a [u8],
+    /// The data after the header, up to the total length. It is shorter when
+    /// the packet is truncated, as when quoted in an ICMP error message.
     pub data: &'a [u8],
 }
 
 impl<'a> IpV4Packet<'a> {
+    /// Decodes an IPv4 packet, which may be truncated, as when quoted in an
+    /// ICMP error message, as long as its header is complete. Only ICMP
+    /// packets are decoded, and of a fragmented one only the first fragment.
     pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        Self::decode_with(data, false)
+    }
+
+    /// Decodes an IPv4 packet as received on a RAW socket. macOS hands it
+    /// over with the total length and the flags and fragment offset in host
+    /// byte order, and the length without the header.
+    pub fn decode_received(data: &'a [u8]) -> Result<Self, Error> {
+        Self::decode_with(data, cfg!(target_vendor = "apple"))
+    }
+
+    fn decode_with(data: &'a [u8], host_order: bool) -> Result<Self, Error> {
         if data.len() < MINIMUM_PACKET_SIZE {
             return Err(Error::TooSmallHeader);
         }
@@ -48,7 +104,7 @@ impl<'a> IpV4Packet<'a> {
             return Err(Error::InvalidVersion);
         }
 
-        if data.len() < header_size {
+        if header_size < MINIMUM_PACKET_SIZE || data.len() < header_size {
             return Err(Error::InvalidHeaderSize);
         }
 
@@ -57,12 +113,294 @@ impl<'a> IpV4Packet<'a> {
             None => return Err(Error::UnknownProtocol),
         };
 
-        let ttl = data[8];
+        // The data of a later fragment does not start with the ICMP header.
+        let flags_and_offset = if host_order {
+            u16::from_ne_bytes([data[6], data[7]])
+        } else {
+            u16::from_be_bytes([data[6], data[7]])
+        };
+        let fragment_offset = (flags_and_offset & 0x1fff) << 3;
+        if fragment_offset != 0 {
+            return Err(Error::NotFirstFragment);
+        }
+
+        let total_length = if host_order {
+            u16::from_ne_bytes([data[2], data[3]]).saturating_add(header_size as u16)
+        } else {
+            u16::from_be_bytes([data[2], data[3]])
+        };
+
+        // Trailing bytes past the announced length, such as link layer
+        // padding, are not part of the packet. A length shorter than the
+        // header is bogus and ignored.
+        let end = match usize::from(total_length) {
+            total_length if total_length >= header_size => data.len().min(total_length),
+            _ => data.len(),
+        };
 
         Ok(Self {
-            protocol: protocol,
-            ttl,
-            data: &data[header_size..],
+            dscp: data[1] >> 2,
+            ecn: data[1] & 0x03,
+            total_length,
+            identification: u16::from_be_bytes([data[4], data[5]]),
+            dont_fragment: flags_and_offset & 0x4000 != 0,
+            more_fragments: flags_and_offset & 0x2000 != 0,
+            fragment_offset,
+            ttl: data[8],
+            protocol,
+            checksum_valid: checksum(&data[..header_size]) == 0,
+            source: Ipv4Addr::new(data[12], data[13], data[14], data[15]),
+            destination: Ipv4Addr::new(data[16], data[17], data[18], data[19]),
+            options: &data[MINIMUM_PACKET_SIZE..header_size],
+            data: &data[header_size..end],
         })
     }
+
+    /// The addresses recorded by the Record Route option, if the header
+    /// carries one.
+    pub fn record_route(&self) -> Option<Vec<Ipv4Addr>> {
+        let option = self.option(OPTION_RECORD_ROUTE)?;
+        // The pointer, counted from 1, is the first free slot.
+        let end = usize::from(*option.get(2)?)
+            .saturating_sub(1)
+            .clamp(3, option.len());
+        Some(option[3..end].chunks_exact(4).map(ipv4_addr).collect())
+    }
+
+    /// The timestamps recorded by the Internet Timestamp option, if the
+    /// header carries one, each with the address of the host that recorded
+    /// it when the option asks for addresses.
+    pub fn timestamps(&self) -> Option<Vec<(Option<Ipv4Addr>, u32)>> {
+        let option = self.option(OPTION_TIMESTAMP)?;
+        let flags = *option.get(3)? & 0x0f;
+        let end = usize::from(option[2])
+            .saturating_sub(1)
+            .clamp(4, option.len());
+        let timestamp = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap());
+        let timestamps = match flags {
+            0 => option[4..end]
+                .chunks_exact(4)
+                .map(|entry| (None, timestamp(entry)))
+                .collect(),
+            _ => option[4..end]
+                .chunks_exact(8)
+                .map(|entry| (Some(ipv4_addr(&entry[..4])), timestamp(&entry[4..])))
+                .collect(),
+        };
+        Some(timestamps)
+    }
+
+    /// The first option of type `kind`, type and length bytes included.
+    fn option(&self, kind: u8) -> Option<&[u8]> {
+        let mut options = self.options;
+        while let Some(&type_) = options.first() {
+            match type_ {
+                OPTION_END => return None,
+                OPTION_NO_OPERATION => options = &options[1..],
+                _ => {
+                    let length = usize::from(*options.get(1)?);
+                    if length < 2 || length > options.len() {
+                        return None;
+                    }
+                    if type_ == kind {
+                        return Some(&options[..length]);
+                    }
+                    options = &options[length..];
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Encodes `option`, padded to a whole number of 32-bit words, to be set with
+/// `IP_OPTIONS`. Room is left for as many entries as fit in the 40 bytes of
+/// options a header can carry; prespecified addresses are limited to 4.
+pub fn encode_option(option: &IpOption) -> Option<Vec<u8>> {
+    let mut encoded = match option {
+        IpOption::RecordRoute => {
+            let mut encoded = vec![OPTION_RECORD_ROUTE, 3 + 9 * 4, 4];
+            encoded.resize(3 + 9 * 4, 0);
+            encoded
+        }
+        IpOption::Timestamps => {
+            let mut encoded = vec![OPTION_TIMESTAMP, 4 + 9 * 4, 5, 0];
+            encoded.resize(4 + 9 * 4, 0);
+            encoded
+        }
+        IpOption::TimestampsAndAddresses => {
+            let mut encoded = vec![OPTION_TIMESTAMP, 4 + 4 * 8, 5, 1];
+            encoded.resize(4 + 4 * 8, 0);
+            encoded
+        }
+        IpOption::PrespecifiedTimestamps(addrs) => {
+            if addrs.is_empty() || addrs.len() > 4 {
+                return None;
+            }
+            let mut encoded = vec![OPTION_TIMESTAMP, 4 + 8 * addrs.len() as u8, 5, 3];
+            for addr in addrs.iter() {
+                encoded.extend_from_slice(&addr.octets());
+                encoded.extend_from_slice(&[0; 4]);
+            }
+            encoded
+        }
+    };
+    encoded.resize(encoded.len().next_multiple_of(4), OPTION_END);
+    Some(encoded)
+}
+
+fn ipv4_addr(bytes: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPv4 packet from 192.0.2.1 to 192.0.2.2 carrying `options` and
+    /// `payload`, with a valid header checksum.
+    fn ipv4(options: &[u8], payload: &[u8]) -> Vec<u8> {
+        let header_size = MINIMUM_PACKET_SIZE + options.len();
+        let total_length = (header_size + payload.len()) as u16;
+        let mut packet = vec![0x40 | (header_size / 4) as u8, 0xb9];
+        packet.extend_from_slice(&total_length.to_be_bytes());
+        packet.extend_from_slice(&[0x12, 0x34, 0x40, 0x00, 57, 1, 0, 0]);
+        packet.extend_from_slice(&[192, 0, 2, 1, 192, 0, 2, 2]);
+        packet.extend_from_slice(options);
+        packet.extend_from_slice(payload);
+        update_checksum(&mut packet);
+        packet
+    }
+
+    fn update_checksum(packet: &mut [u8]) {
+        let header_size = 4 * usize::from(packet[0] & 0x0f);
+        packet[10..12].fill(0);
+        let checksum = checksum(&packet[..header_size]);
+        packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+    }
+
+    #[test]
+    fn decode_header() {
+        let packet = ipv4(&[], &[0, 0, 0, 0]);
+        let decoded = IpV4Packet::decode(&packet).unwrap();
+        assert_eq!(decoded.protocol, IpV4Protocol::Icmp);
+        assert_eq!((decoded.dscp, decoded.ecn), (46, 1));
+        assert_eq!(decoded.total_length, 24);
+        assert_eq!(decoded.identification, 0x1234);
+        assert!(decoded.dont_fragment);
+        assert!(!decoded.more_fragments);
+        assert_eq!(decoded.fragment_offset, 0);
+        assert_eq!(decoded.ttl, 57);
+        assert!(decoded.checksum_valid);
+        assert_eq!(decoded.source, Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(decoded.destination, Ipv4Addr::new(192, 0, 2, 2));
+        assert!(decoded.options.is_empty());
+        assert_eq!(decoded.data, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_options() {
+        // A no-op, then Record Route with two of three slots filled, then
+        // Timestamp with addresses and one entry.
+        let mut options = vec![OPTION_NO_OPERATION, OPTION_RECORD_ROUTE, 15, 12];
+        options.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2, 0, 0, 0, 0]);
+        options.extend_from_slice(&[OPTION_TIMESTAMP, 20, 13, 1]);
+        options.extend_from_slice(&[10, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let packet = ipv4(&options, &[0, 0, 0, 0]);
+
+        let decoded = IpV4Packet::decode(&packet).unwrap();
+        assert_eq!(decoded.options, options);
+        assert_eq!(decoded.data, [0, 0, 0, 0]);
+        assert_eq!(
+            decoded.record_route(),
+            Some(vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)])
+        );
+        assert_eq!(
+            decoded.timestamps(),
+            Some(vec![(Some(Ipv4Addr::new(10, 0, 0, 1)), 256)])
+        );
+    }
+
+    #[test]
+    fn decode_without_options() {
+        let packet = ipv4(&[OPTION_END, 0, 0, 0], &[0, 0, 0, 0]);
+        let decoded = IpV4Packet::decode(&packet).unwrap();
+        assert_eq!(decoded.record_route(), None);
+        assert_eq!(decoded.timestamps(), None);
+    }
+
+    #[test]
+    fn decode_fragment() {
+        // The first fragment, with more to come, is decoded.
+        let mut packet = ipv4(&[], &[0, 0, 0, 0]);
+        packet[6] = 0x20;
+        update_checksum(&mut packet);
+        let decoded = IpV4Packet::decode(&packet).unwrap();
+        assert!(!decoded.dont_fragment);
+        assert!(decoded.more_fragments);
+        assert_eq!(decoded.fragment_offset, 0);
+
+        // A later one, at offset 8, is not.
+        packet[7] = 1;
+        update_checksum(&mut packet);
+        assert!(matches!(
+            IpV4Packet::decode(&packet),
+            Err(Error::NotFirstFragment)
+        ));
+    }
+
+    #[test]
+    fn decode_host_order() {
+        // As macOS hands it to RAW sockets: DF set and 8 bytes of data.
+        let mut packet = ipv4(&[OPTION_NO_OPERATION; 4], &[0; 8]);
+        packet[2..4].copy_from_slice(&8u16.to_ne_bytes());
+        packet[6..8].copy_from_slice(&0x4000u16.to_ne_bytes());
+        packet.extend_from_slice(&[0xff; 4]);
+
+        let decoded = IpV4Packet::decode_with(&packet, true).unwrap();
+        assert_eq!(decoded.total_length, 32);
+        assert!(decoded.dont_fragment);
+        assert_eq!(decoded.fragment_offset, 0);
+        assert_eq!(decoded.data, [0; 8]);
+    }
+
+    #[test]
+    fn decode_bad_checksum() {
+        let mut packet = ipv4(&[], &[0, 0, 0, 0]);
+        packet[8] -= 1;
+        let decoded = IpV4Packet::decode(&packet).unwrap();
+        assert!(!decoded.checksum_valid);
+        assert_eq!(decoded.ttl, 56);
+    }
+
+    #[test]
+    fn decode_total_length() {
+        // Link layer padding past the total length is cut off.
+        let mut packet = ipv4(&[], &[0, 0, 0, 0]);
+        packet.extend_from_slice(&[0xff; 6]);
+        assert_eq!(IpV4Packet::decode(&packet).unwrap().data, [0, 0, 0, 0]);
+
+        // A packet truncated before its total length keeps what is there.
+        let packet = ipv4(&[], &[0; 64]);
+        assert_eq!(IpV4Packet::decode(&packet[..28]).unwrap().data, [0; 8]);
+
+        // A total length shorter than the header is ignored.
+        let mut packet = ipv4(&[], &[0, 0, 0, 0]);
+        packet[2..4].copy_from_slice(&8u16.to_be_bytes());
+        update_checksum(&mut packet);
+        assert_eq!(IpV4Packet::decode(&packet).unwrap().data, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_truncated_header() {
+        let packet = ipv4(&[OPTION_NO_OPERATION; 4], &[]);
+        assert!(matches!(
+            IpV4Packet::decode(&packet[..20]),
+            Err(Error::InvalidHeaderSize)
+        ));
+        assert!(matches!(
+            IpV4Packet::decode(&packet[..19]),
+            Err(Error::TooSmallHeader)
+        ));
+    }
 }
@@ -0,0 +1,249 @@
+use std::net::Ipv6Addr;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("too small header")]
+    TooSmallHeader,
+    #[error("invalid version")]
+    InvalidVersion,
+    #[error("not the first fragment")]
+    NotFirstFragment,
+    #[error("unknown protocol")]
+    UnknownProtocol,
+}
+
+const HEADER_SIZE: usize = 40;
+
+const HOP_BY_HOP_OPTIONS: u8 = 0;
+const ROUTING: u8 = 43;
+const FRAGMENT: u8 = 44;
+const DESTINATION_OPTIONS: u8 = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum IpV6Protocol {
+    Icmp,
+}
+
+impl IpV6Protocol {
+    fn decode(data: u8) -> Option<Self> {
+        match data {
+            58 => Some(IpV6Protocol::Icmp),
+            _ => None,
+        }
+    }
+}
+
+pub struct IpV6Packet<'a> {
+    #[allow(unused)]
+    pub protocol: IpV6Protocol,
+    /// The traffic class, DSCP in the upper 6 bits and ECN in the lower 2.
+    #[allow(unused)]
+    pub traffic_class: u8,
+    /// The 20-bit flow label.
+    #[allow(unused)]
+    pub flow_label: u32,
+    #[allow(unused)]
+    pub hop_limit: u8,
+    pub destination: Ipv6Addr,
+    /// The upper-layer data, after any extension headers.
+    pub data: &'a [u8],
+}
+
+impl<'a> IpV6Packet<'a> {
+    /// Decodes an IPv6 packet, skipping the hop-by-hop options, routing,
+    /// fragment and destination options extension headers in front of the
+    /// upper-layer data. The packet may be truncated, as when quoted in an
+    /// ICMPv6 error message, as long as the extension headers are complete.
+    ///
+    /// Only ICMPv6 packets are decoded, and of a fragmented one only the
+    /// first fragment.
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::TooSmallHeader);
+        }
+
+        let version = data[0] >> 4;
+        if version != 6 {
+            return Err(Error::InvalidVersion);
+        }
+
+        let traffic_class = (data[0] << 4) | (data[1] >> 4);
+        let flow_label = u32::from_be_bytes([0, data[1] & 0x0f, data[2], data[3]]);
+        let mut next_header = data[6];
+        let hop_limit = data[7];
+        let destination: [u8; 16] = data[24..40].try_into().unwrap();
+
+        // A payload length of zero announces a jumbogram, whose length is in
+        // a hop-by-hop option; the data is then taken as it is.
+        let payload_length = usize::from(u16::from_be_bytes([data[4], data[5]]));
+        let end = match payload_length {
+            0 => data.len(),
+            payload_length => data.len().min(HEADER_SIZE + payload_length),
+        };
+        let mut data = &data[HEADER_SIZE..end];
+
+        loop {
+            let header_size = match next_header {
+                HOP_BY_HOP_OPTIONS | ROUTING | DESTINATION_OPTIONS => {
+                    if data.len() < 2 {
+                        return Err(Error::TooSmallHeader);
+                    }
+                    8 * (usize::from(data[1]) + 1)
+                }
+                FRAGMENT => {
+                    if data.len() < 8 {
+                        return Err(Error::TooSmallHeader);
+                    }
+                    let fragment_offset = u16::from_be_bytes([data[2], data[3]]) >> 3;
+                    if fragment_offset != 0 {
+                        return Err(Error::NotFirstFragment);
+                    }
+                    8
+                }
+                _ => break,
+            };
+            if data.len() < header_size {
+                return Err(Error::TooSmallHeader);
+            }
+            next_header = data[0];
+            data = &data[header_size..];
+        }
+
+        let protocol = match IpV6Protocol::decode(next_header) {
+            Some(protocol) => protocol,
+            None => return Err(Error::UnknownProtocol),
+        };
+
+        Ok(Self {
+            protocol,
+            traffic_class,
+            flow_label,
+            hop_limit,
+            destination: Ipv6Addr::from(destination),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICMP: u8 = 58;
+
+    /// An IPv6 packet from ::1 to fe80::2 carrying `payload`, with traffic
+    /// class 0xb8, flow label 0x12345 and hop limit 64.
+    fn ipv6(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x6b, 0x81, 0x23, 0x45];
+        packet.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&[next_header, 64]);
+        packet.extend_from_slice(&[0; 15]);
+        packet.push(1);
+        packet.extend_from_slice(&[0xfe, 0x80]);
+        packet.extend_from_slice(&[0; 13]);
+        packet.push(2);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn decode_header() {
+        let packet = ipv6(ICMP, &[128, 0, 0, 0]);
+        let decoded = IpV6Packet::decode(&packet).unwrap();
+        assert_eq!(decoded.protocol, IpV6Protocol::Icmp);
+        assert_eq!(decoded.traffic_class, 0xb8);
+        assert_eq!(decoded.flow_label, 0x12345);
+        assert_eq!(decoded.hop_limit, 64);
+        assert_eq!(decoded.destination, "fe80::2".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(decoded.data, [128, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_extension_headers() {
+        let mut payload = Vec::new();
+        // Hop-by-hop options, 16 bytes, with a PadN option.
+        payload.extend_from_slice(&[ROUTING, 1, 1, 12]);
+        payload.extend_from_slice(&[0; 12]);
+        // Routing, 8 bytes, no segments left.
+        payload.extend_from_slice(&[FRAGMENT, 0, 4, 0, 0, 0, 0, 0]);
+        // Fragment, offset 0 with more fragments to come.
+        payload.extend_from_slice(&[DESTINATION_OPTIONS, 0, 0, 1, 0, 0, 0, 42]);
+        // Destination options, 8 bytes, with a PadN option.
+        payload.extend_from_slice(&[ICMP, 0, 1, 4, 0, 0, 0, 0]);
+        payload.extend_from_slice(&[128, 0, 0, 0]);
+        let packet = ipv6(HOP_BY_HOP_OPTIONS, &payload);
+
+        let decoded = IpV6Packet::decode(&packet).unwrap();
+        assert_eq!(decoded.protocol, IpV6Protocol::Icmp);
+        assert_eq!(decoded.data, [128, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_trailing_bytes() {
+        let mut packet = ipv6(ICMP, &[128, 0, 0, 0]);
+        packet.extend_from_slice(&[0xff; 4]);
+        assert_eq!(IpV6Packet::decode(&packet).unwrap().data, [128, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode_later_fragment() {
+        // Offset 8 bytes.
+        let payload = [ICMP, 0, 0, 8, 0, 0, 0, 42, 0, 0, 0, 0];
+        let packet = ipv6(FRAGMENT, &payload);
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::NotFirstFragment)
+        ));
+    }
+
+    #[test]
+    fn decode_truncated_extension_header() {
+        // The hop-by-hop options announce 16 bytes, but only 8 are there.
+        let payload = [ICMP, 1, 1, 4, 0, 0, 0, 0];
+        let packet = ipv6(HOP_BY_HOP_OPTIONS, &payload);
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::TooSmallHeader)
+        ));
+
+        let packet = ipv6(FRAGMENT, &[ICMP, 0, 0, 0]);
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::TooSmallHeader)
+        ));
+
+        let packet = ipv6(ICMP, &[]);
+        assert!(matches!(
+            IpV6Packet::decode(&packet[..39]),
+            Err(Error::TooSmallHeader)
+        ));
+    }
+
+    #[test]
+    fn decode_unknown_next_header() {
+        // TCP, and No Next Header behind a destination options header.
+        let packet = ipv6(6, &[0; 20]);
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::UnknownProtocol)
+        ));
+
+        let packet = ipv6(DESTINATION_OPTIONS, &[59, 0, 1, 4, 0, 0, 0, 0]);
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::UnknownProtocol)
+        ));
+    }
+
+    #[test]
+    fn decode_other_version() {
+        let mut packet = ipv6(ICMP, &[128, 0, 0, 0]);
+        packet[0] = 0x45;
+        assert!(matches!(
+            IpV6Packet::decode(&packet),
+            Err(Error::InvalidVersion)
+        ));
+    }
+}
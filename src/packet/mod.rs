@@ -1,6 +1,30 @@
 mod icmp;
 mod ipv4;
+mod ipv6;
 
-pub use self::icmp::{EchoReply, EchoRequest, HEADER_SIZE as ICMP_HEADER_SIZE, IcmpV4, IcmpV6};
+pub use self::icmp::{
+    EchoReply, EchoRequest, ErrorKind, ErrorMessage, HEADER_SIZE as ICMP_HEADER_SIZE, IcmpV4,
+    IcmpV6, Proto, is_checksum_valid,
+};
 
-pub use self::ipv4::IpV4Packet;
+pub use self::ipv4::{IpV4Packet, encode_option};
+pub use self::ipv6::IpV6Packet;
+
+/// The Internet checksum of RFC 1071. Zero over data that includes its own
+/// valid checksum.
+fn checksum(buffer: &[u8]) -> u16 {
+    let mut sum = 0u32;
+    for word in buffer.chunks(2) {
+        let mut part = u16::from(word[0]) << 8;
+        if word.len() > 1 {
+            part += u16::from(word[1]);
+        }
+        sum = sum.wrapping_add(u32::from(part));
+    }
+
+    while (sum >> 16) > 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !sum as u16
+}
@@ -1,14 +1,28 @@
-use std::net::{IpAddr, SocketAddr};
-use std::time::{Duration, SystemTime};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV6};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 
 use rand::random;
 use socket2::{Domain, Protocol, Socket, Type};
 
+use crate::clock::{Clock, MonotonicClock};
 use crate::errors::Error;
-use crate::packet::{EchoReply, EchoRequest, ICMP_HEADER_SIZE, IcmpV4, IcmpV6, IpV4Packet};
+use crate::packet::{
+    EchoReply, EchoRequest, ErrorKind, ErrorMessage, ICMP_HEADER_SIZE, IcmpV4, IcmpV6, IpV4Packet,
+    IpV6Packet, Proto, encode_option, is_checksum_valid,
+};
+use crate::sys::{self, QueuedError, Received};
 
 const TOKEN_SIZE: usize = 24;
-const ECHO_REQUEST_BUFFER_SIZE: usize = ICMP_HEADER_SIZE + TOKEN_SIZE;
+/// The largest echo payloads that fit in an IPv4 datagram after the minimal
+/// header, and in an IPv6 payload.
+const MAX_PAYLOAD_SIZE_V4: usize = 65507;
+const MAX_PAYLOAD_SIZE_V6: usize = 65527;
+/// The largest IPv4 header, options included, that can precede a reply.
+const MAX_IPV4_HEADER_SIZE: usize = 60;
+const MIN_RECV_BUFFER_SIZE: usize = 2048;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(4);
 type Token = [u8; TOKEN_SIZE];
 
 /// The kind of socket used to send the ICMP request.
@@ -34,6 +48,80 @@ impl From<SocketType> for Type {
     }
 }
 
+/// How the echo payload is padded after its token, set with
+/// [`Ping::payload_pattern`].
+///
+/// The padding is echoed back by the target, and a reply whose padding came
+/// back altered is flagged with [`PingResult::corrupted`].
+#[derive(Clone, Copy, Debug, Default)]
+pub enum PayloadPattern<'a> {
+    /// Pad with zero bytes.
+    #[default]
+    Zeros,
+    /// Pad with fresh random bytes for every request.
+    Random,
+    /// Pad by repeating the given bytes, like `ping -p ff00`. An empty pattern
+    /// pads with zeros.
+    Repeat(&'a [u8]),
+}
+
+/// An IPv4 option carried by the echo request, set with [`Ping::ip_option`].
+///
+/// The target copies the option into its reply, where
+/// [`PingResult::route`] or [`PingResult::ip_timestamps`] report what the
+/// hosts on the way recorded in it, there and back.
+#[derive(Clone, Copy, Debug)]
+pub enum IpOption<'a> {
+    /// Record Route, like `ping -R`: every router appends its address, up to
+    /// 9 of them.
+    RecordRoute,
+    /// Internet Timestamp, like `ping -T tsonly`: every router appends a
+    /// timestamp, up to 9 of them.
+    Timestamps,
+    /// Internet Timestamp, like `ping -T tsandaddr`: every router appends its
+    /// address and a timestamp, up to 4 of them.
+    TimestampsAndAddresses,
+    /// Internet Timestamp, like `ping -T tsprespec`: only the given hosts, 1
+    /// to 4 of them, add a timestamp, in order.
+    PrespecifiedTimestamps(&'a [Ipv4Addr]),
+}
+
+/// A timestamp recorded by the Internet Timestamp option; see
+/// [`IpOption`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct IpTimestamp {
+    /// The host that recorded the timestamp, unless only timestamps were
+    /// asked for.
+    pub addr: Option<Ipv4Addr>,
+    /// Milliseconds since midnight UT. With the high-order bit set, the host
+    /// used a time of its own choosing instead.
+    pub timestamp: u32,
+}
+
+/// The interface a link-local IPv6 target is reached through, set with
+/// [`Ping::scope_id`] or [`Ping::scope_interface`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Scope<'a> {
+    Index(u32),
+    Name(&'a str),
+}
+
+/// Where the timestamps behind [`PingResult::rtt`] came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampSource {
+    /// Both ends were timed in userspace with the [`Ping::clock`]. This
+    /// includes scheduling latency on the local host.
+    Clock,
+    /// The reply's arrival was timestamped by the kernel; the request's
+    /// departure was timed in userspace just before sending it.
+    KernelReceive,
+    /// Both the request's departure and the reply's arrival were timestamped
+    /// by the kernel.
+    Kernel,
+}
+
 /// The outcome of a successful ping, returned by [`Ping::send`].
 #[derive(Debug)]
 #[non_exhaustive]
@@ -51,151 +139,549 @@ pub struct PingResult {
     pub ident: u16,
     /// The sequence number echoed back in the reply.
     pub seq_cnt: u16,
-    /// The payload token echoed back in the reply, used to match it to the
-    /// request.
+    /// The payload echoed back in the reply. It starts with the token used to
+    /// match it to the request, followed by any padding requested with
+    /// [`Ping::payload_size`].
     pub payload: Vec<u8>,
     /// The actual source IP address from the reply packet.
     pub source: IpAddr,
     /// The target address passed to the ping.
     #[deprecated(since = "0.7.1", note = "use `source` instead")]
     pub target: IpAddr,
-    /// The TTL from the reply IP header. Only available for IPv4 RAW sockets;
-    /// `None` for IPv4 DGRAM (Linux, no IP header) and all IPv6 responses.
+    /// The TTL (IPv4) or hop limit (IPv6) the reply arrived with, from its IP
+    /// header or, where that is not received, from the ancillary data of the
+    /// reply. `None` on platforms that report neither, such as Windows for
+    /// IPv6.
     pub ttl: Option<u8>,
+    /// Whether the padding after the token came back different from what was
+    /// sent, which points at data-dependent corruption on the path. See
+    /// [`Ping::payload_pattern`].
+    pub corrupted: bool,
+    /// Whether the ICMPv4 checksum of the reply, or the checksum of its IPv4
+    /// header where that is received, did not add up, so some of it was
+    /// damaged on the way. Such replies are only returned while
+    /// [`Ping::strict_checksums`] is off. Always `false` on IPv6, where the
+    /// kernel drops them.
+    pub bad_checksum: bool,
+    /// The addresses recorded in the Record Route option of the reply, when
+    /// it was requested with [`Ping::ip_option`] and the reply's IP header is
+    /// received, as on [`RAW`](SocketType::RAW) sockets.
+    pub route: Option<Vec<Ipv4Addr>>,
+    /// The timestamps recorded in the Internet Timestamp option of the reply,
+    /// under the same conditions as [`route`](PingResult::route).
+    pub ip_timestamps: Option<Vec<IpTimestamp>>,
+    /// The Differentiated Services Code Point the reply arrived with, the
+    /// upper 6 bits of its traffic class (IPv4 type of service), from its IP
+    /// header or the ancillary data of the reply. `None` where neither is
+    /// available, such as on Windows. See [`Ping::tos`].
+    pub dscp: Option<u8>,
+    /// The Explicit Congestion Notification bits the reply arrived with, the
+    /// lower 2 bits of its traffic class, under the same conditions as
+    /// [`dscp`](PingResult::dscp).
+    pub ecn: Option<u8>,
+    /// The local address the reply was sent to, which is the source address
+    /// of the request: the one set with [`Ping::bind_addr`], or the one the
+    /// system picked. `None` where it is not known, such as on Windows
+    /// without `bind_addr`.
+    pub local_addr: Option<IpAddr>,
+    /// Which timestamps [`rtt`](PingResult::rtt) was measured from. Kernel
+    /// timestamps are only used when enabled with `Ping::kernel_timestamps`.
+    pub timestamp_source: TimestampSource,
 }
 
-#[allow(deprecated)]
-fn ping_with_socktype(
-    socket_type: Type,
-    addr: IpAddr,
-    timeout: Option<Duration>,
-    ttl: Option<u32>,
+/// An encoded echo request, along with what is needed to recognise its reply.
+pub(crate) struct Request {
+    pub(crate) dest: SocketAddr,
+    pub(crate) packet: Vec<u8>,
+    /// The identifier sent, if the kernel leaves it alone; datagram sockets
+    /// may rewrite it.
     ident: Option<u16>,
-    seq_cnt: Option<u16>,
-    payload: Option<&Token>,
-    bind_device: Option<&str>,
-) -> Result<PingResult, Error> {
-    let time_start = SystemTime::now();
+    seq_cnt: u16,
+    token: Vec<u8>,
+    padding: Vec<u8>,
+    strict_checksums: bool,
+    bind_addr: Option<IpAddr>,
+}
 
-    let timeout = match timeout {
-        Some(timeout) => timeout,
-        None => Duration::from_secs(4),
-    };
+impl Request {
+    /// The size of a buffer large enough for the reply, IP header included.
+    fn reply_buffer_size(&self) -> usize {
+        MIN_RECV_BUFFER_SIZE.max(MAX_IPV4_HEADER_SIZE + self.packet.len())
+    }
 
-    let dest = SocketAddr::new(addr, 0);
-    let mut buffer = [0; ECHO_REQUEST_BUFFER_SIZE];
+    /// Decodes a received datagram and returns the result if it is the reply
+    /// to this request, or the error if it is an ICMP error message about it.
+    ///
+    /// Anything else (malformed, truncated, or unrelated ICMP traffic from
+    /// other hosts on a RAW socket) yields `None`, so the caller keeps waiting.
+    #[allow(deprecated)]
+    pub(crate) fn match_reply(
+        &self,
+        buffer: &[u8],
+        received: &Received,
+        rtt: Duration,
+    ) -> Option<Result<PingResult, Error>> {
+        let buffer = &buffer[..received.len];
+        if let Some(error) = &received.error {
+            return self.match_queued_error(buffer, received, error).map(Err);
+        }
 
-    let default_payload: &Token = &random();
+        let mut ipv4_packet = None;
+        let icmp = if self.dest.is_ipv4() {
+            // DGRAM socket on Linux may return pure ICMP packet without IP
+            // header. An IPv4 header starts with version 4, which no ICMP type
+            // in use does.
+            if buffer.first().is_some_and(|byte| byte >> 4 != 4) {
+                buffer
+            } else {
+                let packet = IpV4Packet::decode_received(buffer).ok()?;
+                let data = packet.data;
+                ipv4_packet = Some(packet);
+                data
+            }
+        } else {
+            buffer
+        };
+        // macOS hands the IP header to RAW sockets with its length and offset
+        // in host byte order, so only the ICMP checksum can be checked there.
+        let bad_header = !cfg!(target_vendor = "apple")
+            && ipv4_packet
+                .as_ref()
+                .is_some_and(|packet| !packet.checksum_valid);
+        let bad_checksum = bad_header || (self.dest.is_ipv4() && !is_checksum_valid(icmp));
+        if bad_checksum && self.strict_checksums {
+            return None;
+        }
+        let reply = if self.dest.is_ipv4() {
+            EchoReply::decode::<IcmpV4>(icmp)
+        } else {
+            EchoReply::decode::<IcmpV6>(icmp)
+        };
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(_) => return self.match_error(icmp, received).map(Err),
+        };
 
-    let request = EchoRequest {
-        ident: ident.unwrap_or(random()),
-        seq_cnt: seq_cnt.unwrap_or(1),
-        payload: payload.unwrap_or(default_payload),
-    };
+        // The token may be short or empty with a small payload size, so the
+        // identifier also counts where the kernel leaves it alone.
+        if !reply.payload.starts_with(&self.token)
+            || reply.seq_cnt != self.seq_cnt
+            || self.ident.is_some_and(|ident| ident != reply.ident)
+        {
+            return None;
+        }
+
+        // payload token matched: this reply belongs to our request
+        let padding = &reply.payload[self.token.len()..];
+        let traffic_class = match &ipv4_packet {
+            Some(packet) => Some((packet.dscp, packet.ecn)),
+            None => received
+                .traffic_class
+                .map(|value| (value >> 2, value & 0x03)),
+        };
+        Some(Ok(PingResult {
+            rtt,
+            ident: reply.ident,
+            seq_cnt: reply.seq_cnt,
+            payload: reply.payload.to_vec(),
+            source: received.source.unwrap_or(self.dest.ip()),
+            target: self.dest.ip(),
+            ttl: ipv4_packet
+                .as_ref()
+                .map(|packet| packet.ttl)
+                .or(received.ttl),
+            corrupted: padding != self.padding,
+            bad_checksum,
+            local_addr: ipv4_packet
+                .as_ref()
+                .map(|packet| IpAddr::V4(packet.destination))
+                .or(received.destination)
+                .or(self.bind_addr),
+            dscp: traffic_class.map(|(dscp, _)| dscp),
+            ecn: traffic_class.map(|(_, ecn)| ecn),
+            route: ipv4_packet.as_ref().and_then(IpV4Packet::record_route),
+            ip_timestamps: ipv4_packet.as_ref().and_then(|packet| {
+                let timestamps = packet.timestamps()?;
+                Some(
+                    timestamps
+                        .into_iter()
+                        .map(|(addr, timestamp)| IpTimestamp { addr, timestamp })
+                        .collect(),
+                )
+            }),
+            timestamp_source: TimestampSource::Clock,
+        }))
+    }
+
+    /// Decodes an ICMP error message and returns it as an [`Error`] if the
+    /// request it quotes is this one.
+    fn match_error(&self, icmp: &[u8], received: &Received) -> Option<Error> {
+        let (message, destination, original) = if self.dest.is_ipv4() {
+            let message = ErrorMessage::decode::<IcmpV4>(icmp).ok()?;
+            let ipv4_packet = IpV4Packet::decode(message.original).ok()?;
+            let original = EchoRequest::decode::<IcmpV4>(ipv4_packet.data).ok()?;
+            (message, IpAddr::V4(ipv4_packet.destination), original)
+        } else {
+            let message = ErrorMessage::decode::<IcmpV6>(icmp).ok()?;
+            let ipv6_packet = IpV6Packet::decode(message.original).ok()?;
+            let original = EchoRequest::decode::<IcmpV6>(ipv6_packet.data).ok()?;
+            (message, IpAddr::V6(ipv6_packet.destination), original)
+        };
 
-    let socket = if dest.is_ipv4() {
-        if request.encode::<IcmpV4>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError.into());
+        if !self.is_quoted(destination, &original) {
+            return None;
         }
-        Socket::new(Domain::IPV4, socket_type, Some(Protocol::ICMPV4))?
-    } else {
-        if request.encode::<IcmpV6>(&mut buffer[..]).is_err() {
-            return Err(Error::InternalError.into());
+
+        let reporter = received.source.unwrap_or(self.dest.ip());
+        Some(icmp_error(
+            message.kind,
+            message.code,
+            message.mtu,
+            reporter,
+        ))
+    }
+
+    /// Returns an ICMP error the kernel took off the error queue as an
+    /// [`Error`] if the request it quotes, held in `buffer`, is this one.
+    fn match_queued_error(
+        &self,
+        buffer: &[u8],
+        received: &Received,
+        error: &QueuedError,
+    ) -> Option<Error> {
+        let (kind, original) = if self.dest.is_ipv4() {
+            let kind = IcmpV4::error_kind(error.icmp_type, error.code)?;
+            (kind, EchoRequest::decode::<IcmpV4>(buffer).ok()?)
+        } else {
+            let kind = IcmpV6::error_kind(error.icmp_type, error.code)?;
+            (kind, EchoRequest::decode::<IcmpV6>(buffer).ok()?)
+        };
+        if !self.is_quoted(received.source?, &original) {
+            return None;
         }
-        Socket::new(Domain::IPV6, socket_type, Some(Protocol::ICMPV6))?
-    };
 
-    if dest.is_ipv4() {
-        socket.set_ttl_v4(ttl.unwrap_or(64))?;
-    } else {
-        socket.set_unicast_hops_v6(ttl.unwrap_or(64))?;
+        let reporter = error.offender.unwrap_or(self.dest.ip());
+        Some(icmp_error(kind, error.code, Some(error.info), reporter))
     }
 
-    #[allow(unused)]
-    if let Some(device) = bind_device {
-        #[cfg(any(target_os = "linux", target_os = "android"))]
-        {
-            socket.bind_device(Some(device.as_bytes()))?;
+    /// Whether `original`, quoted in an ICMP error about a request sent to
+    /// `destination`, is this request.
+    fn is_quoted(&self, destination: IpAddr, original: &EchoRequest) -> bool {
+        // Routers may quote as little as 8 bytes of the request, so compare
+        // whatever part of the token made it.
+        let quoted = original.payload.len().min(self.token.len());
+        destination == self.dest.ip()
+            && self.ident.is_none_or(|ident| ident == original.ident)
+            && original.seq_cnt == self.seq_cnt
+            && original.payload[..quoted] == self.token[..quoted]
+    }
+}
+
+/// The [`Error`] for an ICMP error message of `kind` sent by `reporter`.
+fn icmp_error(kind: ErrorKind, code: u8, mtu: Option<u32>, reporter: IpAddr) -> Error {
+    match kind {
+        ErrorKind::DestinationUnreachable => Error::DestinationUnreachable { reporter, code },
+        ErrorKind::PacketTooBig => Error::PacketTooBig {
+            reporter,
+            mtu: mtu.unwrap_or(0),
+        },
+        ErrorKind::TimeExceeded => Error::TimeExceeded { reporter, code },
+        ErrorKind::ParameterProblem => Error::ParameterProblem { reporter, code },
+    }
+}
+
+/// Switches `result` to the round-trip time given by kernel timestamps, when
+/// the reply carries a receive timestamp.
+///
+/// `transmitted` is the kernel's transmit timestamp if it reported one; else
+/// `sent`, the wall clock time taken just before sending, stands in for it.
+/// Kernel timestamps follow the wall clock, so if it stepped backwards in
+/// between, the clock measured RTT is kept.
+fn use_kernel_timestamps(
+    result: &mut PingResult,
+    received: &Received,
+    transmitted: Option<SystemTime>,
+    sent: SystemTime,
+) {
+    let Some(time_received) = received.timestamp else {
+        return;
+    };
+    let (time_sent, source) = match transmitted {
+        Some(transmitted) => (transmitted, TimestampSource::Kernel),
+        None => (sent, TimestampSource::KernelReceive),
+    };
+    if let Ok(rtt) = time_received.duration_since(time_sent) {
+        result.rtt = rtt;
+        result.timestamp_source = source;
+    }
+}
+
+/// An echo request on its way, awaiting its reply.
+pub(crate) struct Outstanding {
+    /// Tells the request apart in the outcomes of [`Awaiting`].
+    index: usize,
+    pub(crate) request: Request,
+    time_sent: Instant,
+    wall_sent: SystemTime,
+    /// The kernel's transmit timestamp of the request, once reported.
+    transmitted: Option<SystemTime>,
+}
+
+impl Outstanding {
+    /// A request sent at `time_sent`, a reading of the clock taken right
+    /// before sending it.
+    pub(crate) fn new(index: usize, request: Request, time_sent: Instant) -> Self {
+        Outstanding {
+            index,
+            request,
+            time_sent,
+            wall_sent: SystemTime::now(),
+            transmitted: None,
         }
-        #[cfg(not(any(target_os = "linux", target_os = "android")))]
-        {
-            eprintln!("Warning: bind_device is only supported on Linux and Android platforms");
+    }
+}
+
+/// The echo requests sent on one socket that await their replies, up to a
+/// deadline.
+///
+/// This is the receiving side of every exchange, blocking, async and
+/// [`send_many`](crate::Pinger::send_many) alike: each datagram read from the
+/// socket into [`buffer`](Awaiting::buffer) goes through
+/// [`receive`](Awaiting::receive), which matches it to its request and times
+/// it.
+pub(crate) struct Awaiting {
+    outstanding: Vec<Outstanding>,
+    deadline: Instant,
+    /// The last reading of the clock. It is only read when a request is sent
+    /// and when a datagram arrives.
+    now: Instant,
+    /// Whether a reply must come from the target of its request, to tell
+    /// apart the replies of a batch whose requests may share a token.
+    match_source: bool,
+    /// The buffer datagrams are read into.
+    pub(crate) buffer: Vec<u8>,
+}
+
+impl Awaiting {
+    /// Awaits replies for `timeout` from `start`, a reading of the clock.
+    pub(crate) fn new(start: Instant, timeout: Duration, match_source: bool) -> Self {
+        Awaiting {
+            outstanding: Vec::new(),
+            deadline: start + timeout,
+            now: start,
+            match_source,
+            buffer: Vec::new(),
         }
     }
 
-    socket.set_write_timeout(Some(timeout))?;
+    /// Awaits the reply to a request that was just sent.
+    pub(crate) fn push(&mut self, outstanding: Outstanding) {
+        let size = outstanding.request.reply_buffer_size();
+        if self.buffer.len() < size {
+            self.buffer.resize(size, 0);
+        }
+        self.outstanding.push(outstanding);
+    }
 
-    socket.send_to(&mut buffer, &dest.into())?;
+    /// Whether no request awaits its reply.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.outstanding.is_empty()
+    }
 
-    // loop until either an echo whose payload token matches was received or timeout is over
-    let mut elapsed_time = Duration::from_secs(0);
-    loop {
-        socket.set_read_timeout(Some(timeout - elapsed_time))?;
+    /// How long is left until the deadline, as of the last datagram, or
+    /// `None` once it passed or every request got its answer.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        if self.outstanding.is_empty() {
+            return None;
+        }
+        let remaining = self.deadline.saturating_duration_since(self.now);
+        (!remaining.is_zero()).then_some(remaining)
+    }
 
-        let mut buffer: [u8; 2048] = [0; 2048];
-        // socket2 0.6 recv_from requires &mut [MaybeUninit<u8>]; cast is sound
-        // because MaybeUninit<u8> has the same layout as u8.
-        let (n, src_addr) = socket.recv_from(unsafe {
-            std::slice::from_raw_parts_mut(
-                buffer.as_mut_ptr() as *mut std::mem::MaybeUninit<u8>,
-                buffer.len(),
-            )
-        })?;
-        let source_ip = src_addr.as_socket().map(|s| s.ip()).unwrap_or(addr);
-
-        let mut recv_ttl: Option<u8> = None;
-        let reply = if dest.is_ipv4() {
-            // DGRAM socket on Linux may return pure ICMP packet without IP header.
-            if n == ECHO_REQUEST_BUFFER_SIZE {
-                match EchoReply::decode::<IcmpV4>(&buffer[..n]) {
-                    Ok(reply) => reply,
-                    Err(_) => continue,
-                }
-            } else {
-                // Skip undecodable IP packets (malformed, truncated, or
-                // unrelated ICMP traffic from other hosts on a RAW socket)
-                // instead of failing the whole ping; keep waiting for our reply.
-                let ipv4_packet = match IpV4Packet::decode(&buffer[..n]) {
-                    Ok(packet) => packet,
-                    Err(_) => continue,
-                };
-                recv_ttl = Some(ipv4_packet.ttl);
-                match EchoReply::decode::<IcmpV4>(ipv4_packet.data) {
-                    Ok(reply) => reply,
-                    Err(_) => continue,
-                }
+    /// Matches the datagram just read into the buffer, described by
+    /// `received`, to the requests, and returns the outcome of the one it
+    /// answers, by its index: its reply, or the ICMP error about it. That
+    /// request is no longer awaited.
+    ///
+    /// A datagram read after the deadline answers nothing, so a late reply
+    /// counts as a timeout. Transmit timestamps are handed to the requests in
+    /// the order they were sent, which is the order the kernel reports them
+    /// in.
+    pub(crate) fn receive(
+        &mut self,
+        socket: &Socket,
+        received: &Received,
+        clock: &dyn Clock,
+    ) -> Option<(usize, Result<PingResult, Error>)> {
+        let time_received = clock.now();
+        self.now = time_received;
+        if time_received >= self.deadline {
+            return None;
+        }
+        if let Some(transmitted) = received.transmitted {
+            let untimed = self
+                .outstanding
+                .iter_mut()
+                .find(|outstanding| outstanding.transmitted.is_none());
+            if let Some(outstanding) = untimed {
+                outstanding.transmitted = Some(transmitted);
             }
-        } else {
-            match EchoReply::decode::<IcmpV6>(&buffer[..n]) {
-                Ok(reply) => reply,
-                Err(_) => continue,
+        }
+
+        let (position, result) =
+            self.outstanding
+                .iter()
+                .enumerate()
+                .find_map(|(position, outstanding)| {
+                    let request = &outstanding.request;
+                    let rtt = time_received.saturating_duration_since(outstanding.time_sent);
+                    match request.match_reply(&self.buffer, received, rtt)? {
+                        // ICMP errors come from routers, but replies must come
+                        // from the target itself.
+                        Ok(result) if self.match_source && result.source != request.dest.ip() => {
+                            None
+                        }
+                        result => Some((position, result)),
+                    }
+                })?;
+        let mut outstanding = self.outstanding.remove(position);
+
+        let result = result.map(|mut result| {
+            // The next transmit timestamp on the error queue can only be
+            // taken when no other request may own it.
+            if received.timestamp.is_some()
+                && outstanding.transmitted.is_none()
+                && self.outstanding.is_empty()
+            {
+                outstanding.transmitted = sys::transmit_timestamp(socket);
             }
-        };
+            use_kernel_timestamps(
+                &mut result,
+                received,
+                outstanding.transmitted,
+                outstanding.wall_sent,
+            );
+            result
+        });
+        Some((outstanding.index, result))
+    }
 
-        // update elapsed time before deciding whether the payload token matches
-        elapsed_time = match SystemTime::now().duration_since(time_start) {
-            Ok(reply) => reply,
-            Err(_) => return Err(Error::InternalError.into()),
-        };
+    /// Gives up on the requests still awaiting their replies: they time out.
+    pub(crate) fn into_timeouts(self) -> impl Iterator<Item = (usize, Result<PingResult, Error>)> {
+        self.outstanding
+            .into_iter()
+            .map(|outstanding| (outstanding.index, Err(timed_out())))
+    }
 
-        if reply.payload == request.payload {
-            // payload token matched: this reply belongs to our request
-            return Ok(PingResult {
-                rtt: elapsed_time,
-                ident: reply.ident,
-                seq_cnt: reply.seq_cnt,
-                payload: reply.payload.to_vec(),
-                source: source_ip,
-                target: addr,
-                ttl: recv_ttl,
-            });
-        }
+    /// Fails the requests still awaiting their replies with `error`, a
+    /// socket error.
+    fn into_errors(
+        self,
+        error: std::io::Error,
+    ) -> impl Iterator<Item = (usize, Result<PingResult, Error>)> {
+        self.outstanding.into_iter().map(move |outstanding| {
+            let error = match error.raw_os_error() {
+                Some(code) => std::io::Error::from_raw_os_error(code),
+                None => std::io::Error::new(error.kind(), error.to_string()),
+            };
+            (outstanding.index, Err(error.into()))
+        })
+    }
+}
 
-        if elapsed_time >= timeout {
-            let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout occured");
-            return Err(Error::IoError { error: (error) });
+/// Receives on `socket` until every request of `awaiting` is answered or its
+/// deadline passes, and returns the outcome of each by its index. A socket
+/// error fails every request still awaiting its reply.
+///
+/// Replies to other requests on the same socket are skipped, which lets a
+/// socket be reused across requests.
+pub(crate) fn receive_all(
+    socket: &Socket,
+    mut awaiting: Awaiting,
+    clock: &dyn Clock,
+) -> Vec<(usize, Result<PingResult, Error>)> {
+    let mut outcomes = Vec::new();
+    while let Some(remaining) = awaiting.remaining() {
+        // An expired read timeout surfaces as `WouldBlock` on Unix.
+        let received = socket
+            .set_read_timeout(Some(remaining))
+            .and_then(|_| sys::recv(socket, &mut awaiting.buffer));
+        match received {
+            Ok(received) => outcomes.extend(awaiting.receive(socket, &received, clock)),
+            Err(error) if is_timeout(&error) => break,
+            Err(error) => {
+                outcomes.extend(awaiting.into_errors(error));
+                return outcomes;
+            }
         }
     }
+    outcomes.extend(awaiting.into_timeouts());
+    outcomes
+}
+
+/// Sends `request` on `socket` and blocks until its reply arrives or `timeout`
+/// elapses, as measured by `clock`.
+///
+/// Replies to other requests on the same socket are skipped, which lets a
+/// socket be reused across requests. A reply that arrives after the timeout
+/// counts as a timeout.
+pub(crate) fn exchange(
+    socket: &Socket,
+    request: Request,
+    timeout: Duration,
+    clock: &dyn Clock,
+) -> Result<PingResult, Error> {
+    socket.set_write_timeout(Some(timeout))?;
+    // Leave only this request's transmit timestamp and ICMP errors in the
+    // error queue.
+    sys::drain_error_queue(socket);
+
+    let time_sent = clock.now();
+    let mut awaiting = Awaiting::new(time_sent, timeout, false);
+    let outstanding = Outstanding::new(0, request, time_sent);
+    socket.send_to(
+        &outstanding.request.packet,
+        &outstanding.request.dest.into(),
+    )?;
+    awaiting.push(outstanding);
+
+    // There is one outcome per request.
+    let (_, result) = receive_all(socket, awaiting, clock).remove(0);
+    result
+}
+
+/// Whether `error` is a socket read or write timing out.
+pub(crate) fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// The error returned when no matching reply arrived in time.
+pub(crate) fn timed_out() -> Error {
+    let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "Timeout occured");
+    Error::IoError { error }
+}
+
+fn ping_with_socktype(
+    socket_type: SocketType,
+    addr: IpAddr,
+    timeout: Option<Duration>,
+    ttl: Option<u32>,
+    ident: Option<u16>,
+    seq_cnt: Option<u16>,
+    payload: Option<&Token>,
+) -> Result<(), Error> {
+    let mut ping = Ping::new(addr);
+    ping.socket_type = socket_type;
+    ping.timeout = timeout;
+    ping.ttl = ttl;
+    ping.ident = ident;
+    ping.seq_cnt = seq_cnt;
+    ping.payload = payload;
+    ping.send()?;
+    Ok(())
 }
 
 pub mod rawsock {
@@ -208,8 +694,7 @@ pub mod rawsock {
         seq_cnt: Option<u16>,
         payload: Option<&Token>,
     ) -> Result<(), Error> {
-        ping_with_socktype(Type::RAW, addr, timeout, ttl, ident, seq_cnt, payload, None)?;
-        Ok(())
+        ping_with_socktype(SocketType::RAW, addr, timeout, ttl, ident, seq_cnt, payload)
     }
 }
 
@@ -224,16 +709,14 @@ pub mod dgramsock {
         payload: Option<&Token>,
     ) -> Result<(), Error> {
         ping_with_socktype(
-            Type::DGRAM,
+            SocketType::DGRAM,
             addr,
             timeout,
             ttl,
             ident,
             seq_cnt,
             payload,
-            None,
-        )?;
-        Ok(())
+        )
     }
 }
 
@@ -261,17 +744,37 @@ pub fn ping(
 /// ```
 #[derive(Debug, Clone)]
 pub struct Ping<'a> {
-    socket_type: SocketType,
-    addr: IpAddr,
-    timeout: Option<Duration>,
-    ttl: Option<u32>,
-    ident: Option<u16>,
-    seq_cnt: Option<u16>,
-    payload: Option<&'a Token>,
+    pub(crate) socket_type: SocketType,
+    pub(crate) addr: IpAddr,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) ttl: Option<u32>,
+    pub(crate) ident: Option<u16>,
+    pub(crate) seq_cnt: Option<u16>,
+    pub(crate) payload: Option<&'a Token>,
+    pub(crate) payload_size: Option<usize>,
+    pub(crate) payload_pattern: PayloadPattern<'a>,
     #[cfg(any(target_os = "linux", target_os = "android"))]
-    bind_device: Option<&'a str>,
+    pub(crate) bind_device: Option<&'a str>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) mark: Option<u32>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) netns: Option<&'a Path>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) kernel_timestamps: bool,
+    pub(crate) dont_fragment: bool,
+    pub(crate) strict_checksums: bool,
+    pub(crate) ip_option: Option<IpOption<'a>>,
+    pub(crate) tos: Option<u8>,
+    pub(crate) bind_addr: Option<IpAddr>,
+    pub(crate) scope: Option<Scope<'a>>,
+    pub(crate) count: Option<usize>,
+    pub(crate) interval: Option<Duration>,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) clock: Option<&'a dyn Clock>,
 }
 
+// The builder methods end with `return self;` throughout.
+#[allow(clippy::needless_return)]
 impl<'a> Ping<'a> {
     /// Creates a builder targeting `addr`, with the default socket type for
     /// the current platform ([`RAW`](SocketType::RAW) on Windows,
@@ -290,11 +793,38 @@ impl<'a> Ping<'a> {
             ident: None,
             seq_cnt: None,
             payload: None,
+            payload_size: None,
+            payload_pattern: PayloadPattern::Zeros,
             #[cfg(any(target_os = "linux", target_os = "android"))]
             bind_device: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            mark: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            netns: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            kernel_timestamps: false,
+            dont_fragment: false,
+            strict_checksums: false,
+            ip_option: None,
+            tos: None,
+            bind_addr: None,
+            scope: None,
+            count: None,
+            interval: None,
+            deadline: None,
+            clock: None,
         };
     }
 
+    /// Creates a builder targeting an IPv6 socket address, keeping its scope
+    /// id, as needed for link-local targets such as `fe80::1%eth0`. The port
+    /// and flow info are ignored. See [`Ping::scope_id`].
+    pub fn new_scoped(addr: SocketAddrV6) -> Self {
+        let mut ping = Ping::new(IpAddr::V6(*addr.ip()));
+        ping.scope_id(addr.scope_id());
+        return ping;
+    }
+
     /// Overrides the [`SocketType`] used to send the request, replacing the
     /// platform default chosen by [`Ping::new`].
     pub fn socket_type(&mut self, socket_type: SocketType) -> &mut Self {
@@ -302,20 +832,189 @@ impl<'a> Ping<'a> {
         return self;
     }
 
-    fn ping_with_socket(&self, sock_type: Type) -> Result<PingResult, Error> {
-        ping_with_socktype(
-            sock_type,
-            self.addr,
-            self.timeout,
-            self.ttl,
-            self.ident,
-            self.seq_cnt,
-            self.payload,
-            #[cfg(any(target_os = "linux", target_os = "android"))]
-            self.bind_device,
-            #[cfg(not(any(target_os = "linux", target_os = "android")))]
-            None,
-        )
+    /// The reply timeout, falling back to the default of 4 seconds.
+    pub(crate) fn timeout_or_default(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// The configured clock, falling back to [`MonotonicClock`].
+    pub(crate) fn clock_or_default(&self) -> &'a dyn Clock {
+        self.clock.unwrap_or(&MonotonicClock)
+    }
+
+    /// The scope id IPv6 requests are sent with, 0 when unset. An interface
+    /// name is looked up in the namespace set with [`netns`](Ping::netns).
+    fn resolve_scope_id(&self) -> Result<u32, Error> {
+        match self.scope {
+            Some(Scope::Index(index)) => Ok(index),
+            Some(Scope::Name(name)) => {
+                #[cfg(any(target_os = "linux", target_os = "android"))]
+                if let Some(path) = self.netns {
+                    return Ok(sys::in_netns(path, || sys::interface_index(name))?);
+                }
+                Ok(sys::interface_index(name)?)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Encodes the echo request described by this builder.
+    pub(crate) fn request(&self) -> Result<Request, Error> {
+        let dest = match self.addr {
+            IpAddr::V4(_) => SocketAddr::new(self.addr, 0),
+            IpAddr::V6(addr) => SocketAddrV6::new(addr, 0, 0, self.resolve_scope_id()?).into(),
+        };
+        let token: Token = match self.payload {
+            Some(payload) => *payload,
+            None => random(),
+        };
+        let payload_size = self.payload_size.unwrap_or(TOKEN_SIZE);
+        let max_payload_size = match self.addr {
+            IpAddr::V4(_) => MAX_PAYLOAD_SIZE_V4,
+            IpAddr::V6(_) => MAX_PAYLOAD_SIZE_V6,
+        };
+        if payload_size > max_payload_size {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the payload size exceeds the maximum for the address family",
+            )
+            .into());
+        }
+        let token = token[..payload_size.min(TOKEN_SIZE)].to_vec();
+        let mut padding = vec![0; payload_size - token.len()];
+        match self.payload_pattern {
+            PayloadPattern::Zeros => {}
+            PayloadPattern::Random => padding.iter_mut().for_each(|byte| *byte = random()),
+            PayloadPattern::Repeat(pattern) => {
+                for (byte, value) in padding.iter_mut().zip(pattern.iter().cycle()) {
+                    *byte = *value;
+                }
+            }
+        }
+        let payload = [&token[..], &padding[..]].concat();
+
+        let seq_cnt = self.seq_cnt.unwrap_or(1);
+        let ident = self.ident.unwrap_or(random());
+        let request = EchoRequest {
+            ident,
+            seq_cnt,
+            payload: &payload,
+        };
+
+        let mut packet = vec![0; ICMP_HEADER_SIZE + payload_size];
+        let encoded = if dest.is_ipv4() {
+            request.encode::<IcmpV4>(&mut packet[..])
+        } else {
+            request.encode::<IcmpV6>(&mut packet[..])
+        };
+        if encoded.is_err() {
+            return Err(Error::InternalError);
+        }
+
+        Ok(Request {
+            dest,
+            packet,
+            ident: matches!(self.socket_type, SocketType::RAW).then_some(ident),
+            seq_cnt,
+            token,
+            padding,
+            strict_checksums: self.strict_checksums,
+            bind_addr: self.bind_addr,
+        })
+    }
+
+    /// Creates the ICMP socket for the target's address family and applies
+    /// the configured socket options.
+    pub(crate) fn open_socket(&self) -> Result<Socket, Error> {
+        if self
+            .bind_addr
+            .is_some_and(|bind_addr| bind_addr.is_ipv4() != self.addr.is_ipv4())
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the bind address and the target are of different address families",
+            )
+            .into());
+        }
+
+        let socket_type = self.socket_type.into();
+        let (domain, protocol) = if self.addr.is_ipv4() {
+            (Domain::IPV4, Protocol::ICMPV4)
+        } else {
+            (Domain::IPV6, Protocol::ICMPV6)
+        };
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        let socket = match self.netns {
+            Some(path) => sys::in_netns(path, || Socket::new(domain, socket_type, Some(protocol)))?,
+            None => Socket::new(domain, socket_type, Some(protocol))?,
+        };
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let socket = Socket::new(domain, socket_type, Some(protocol))?;
+
+        if self.addr.is_ipv4() {
+            socket.set_ttl_v4(self.ttl.unwrap_or(64))?;
+        } else {
+            socket.set_unicast_hops_v6(self.ttl.unwrap_or(64))?;
+        }
+
+        // A link-local source address is only unique with its interface.
+        if let Some(bind_addr) = self.bind_addr {
+            let bind_addr = match bind_addr {
+                IpAddr::V4(_) => SocketAddr::new(bind_addr, 0),
+                IpAddr::V6(addr) => SocketAddrV6::new(addr, 0, 0, self.resolve_scope_id()?).into(),
+            };
+            socket.bind(&bind_addr.into())?;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(device) = self.bind_device {
+            socket.bind_device(Some(device.as_bytes()))?;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(mark) = self.mark {
+            socket.set_mark(mark).map_err(|error| match error.kind() {
+                std::io::ErrorKind::PermissionDenied => Error::MarkNotPermitted,
+                _ => Error::from(error),
+            })?;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.kernel_timestamps {
+            sys::enable_timestamps(&socket)?;
+        }
+
+        // The values these report are optional, so a kernel that rejects
+        // one leaves the field `None` rather than failing the request.
+        let _ = sys::enable_recv_ttl(&socket, self.addr.is_ipv6());
+        let _ = sys::enable_recv_traffic_class(&socket, self.addr.is_ipv6());
+        let _ = sys::enable_recv_destination(&socket, self.addr.is_ipv6());
+
+        if let Some(tos) = self.tos {
+            sys::set_traffic_class(&socket, self.addr.is_ipv6(), tos)?;
+        }
+
+        if self.dont_fragment {
+            sys::set_dont_fragment(&socket, self.addr.is_ipv6())?;
+        }
+
+        if let (Some(option), IpAddr::V4(_)) = (&self.ip_option, self.addr) {
+            let Some(option) = encode_option(option) else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "1 to 4 prespecified timestamp addresses are supported",
+                )
+                .into());
+            };
+            sys::set_ip_options(&socket, &option)?;
+        }
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if matches!(self.socket_type, SocketType::DGRAM) {
+            sys::enable_recv_errors(&socket, self.addr.is_ipv6())?;
+        }
+
+        Ok(socket)
     }
 
     /// Sets how long [`send`](Ping::send) waits for a reply before failing.
@@ -362,11 +1061,87 @@ impl<'a> Ping<'a> {
     ///
     /// The reply is matched to the request by this token, so it acts as the
     /// correlation id. When unset, a random token is generated for each ping.
+    /// The token starts the payload; see [`payload_size`](Ping::payload_size).
     pub fn payload(&mut self, payload: &'a Token) -> &mut Self {
         self.payload = Some(payload);
         return self;
     }
 
+    /// Sets the type of service byte (IPv4) or traffic class (IPv6) of the
+    /// request, like `ping -Q`.
+    ///
+    /// The upper 6 bits are the DSCP and the lower 2 the ECN bits, so EF is
+    /// `46 << 2`, AF41 `34 << 2` and CS1 `8 << 2`. The reply's marking is
+    /// reported in [`PingResult::dscp`] and [`PingResult::ecn`]. Defaults to 0.
+    /// Setting the IPv6 traffic class fails when sending on platforms other
+    /// than Linux, Android, macOS and FreeBSD.
+    #[doc(alias = "traffic_class")]
+    pub fn tos(&mut self, tos: u8) -> &mut Self {
+        self.tos = Some(tos);
+        return self;
+    }
+
+    /// Sets the size in bytes of the echo payload, like `ping -s`.
+    ///
+    /// The payload starts with the 24-byte token and is padded after it, so
+    /// large sizes can probe the MTU of a path. A size below 24 carries only
+    /// the start of the token, which weakens the matching of replies to
+    /// requests on [`DGRAM`](SocketType::DGRAM) sockets, where the identifier
+    /// is not checked. Defaults to 24 when unset. Sizes above 65507 bytes
+    /// for IPv4 and 65527 for IPv6 fail when sending, with an
+    /// [`Error::IoError`] of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput).
+    pub fn payload_size(&mut self, payload_size: usize) -> &mut Self {
+        self.payload_size = Some(payload_size);
+        return self;
+    }
+
+    /// Sets how the payload is padded after the token, like `ping -p`.
+    ///
+    /// Replies are still matched by the token alone; if the echoed padding
+    /// differs from what was sent, [`PingResult::corrupted`] is set. Only
+    /// takes effect when [`payload_size`](Ping::payload_size) leaves room
+    /// after the token. Defaults to [`PayloadPattern::Zeros`].
+    pub fn payload_pattern(&mut self, payload_pattern: PayloadPattern<'a>) -> &mut Self {
+        self.payload_pattern = payload_pattern;
+        return self;
+    }
+
+    /// Sends the request from a local address, like `ping -I 192.0.2.2`, on
+    /// hosts with several of them.
+    ///
+    /// The socket is bound to the address before sending, so it must be one
+    /// of the host's, of the same address family as the target; sending fails
+    /// otherwise. [`PingResult::local_addr`] reports the address used. A
+    /// link-local IPv6 address is bound on the interface of the target's
+    /// scope, set with [`scope_id`](Ping::scope_id) or
+    /// [`scope_interface`](Ping::scope_interface).
+    pub fn bind_addr(&mut self, bind_addr: IpAddr) -> &mut Self {
+        self.bind_addr = Some(bind_addr);
+        return self;
+    }
+
+    /// Sets the scope id of the IPv6 target: the index of the interface a
+    /// link-local target, like `fe80::1%2`, is reached through.
+    ///
+    /// Link-local addresses are only unique per link, so requests to them
+    /// fail without a scope id. Addresses of other scopes ignore it, as do
+    /// IPv4 targets.
+    pub fn scope_id(&mut self, scope_id: u32) -> &mut Self {
+        self.scope = Some(Scope::Index(scope_id));
+        return self;
+    }
+
+    /// Sets the scope id of the IPv6 target by interface name, like
+    /// `fe80::1%eth0`. The name is looked up when sending, which fails if
+    /// there is no such interface. See [`scope_id`](Ping::scope_id).
+    ///
+    /// Fails when sending on Windows, where interfaces are known by index.
+    pub fn scope_interface(&mut self, interface: &'a str) -> &mut Self {
+        self.scope = Some(Scope::Name(interface));
+        return self;
+    }
+
     /// Binds the socket to a network interface by name (e.g. `"eth0"`), so the
     /// request is sent from that interface.
     ///
@@ -377,6 +1152,127 @@ impl<'a> Ping<'a> {
         return self;
     }
 
+    /// Sets the firewall mark (`SO_MARK`) of the requests, like `ping -m`, so
+    /// policy routing rules and VRFs matching on it pick their route.
+    ///
+    /// Needs the `CAP_NET_ADMIN` capability, or `CAP_NET_RAW` since Linux
+    /// 5.17; without it, sending fails with [`Error::MarkNotPermitted`].
+    ///
+    /// Only available on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn mark(&mut self, mark: u32) -> &mut Self {
+        self.mark = Some(mark);
+        return self;
+    }
+
+    /// Sends from inside the network namespace at `path`, such as
+    /// `/var/run/netns/foo` as created by `ip netns add foo`, without moving
+    /// the process into it.
+    ///
+    /// The socket is created on a short-lived helper thread that joins the
+    /// namespace, then used from the calling thread as usual, by every API
+    /// including [`Pinger`](crate::Pinger) sessions. Joining a namespace
+    /// needs the `CAP_SYS_ADMIN` capability. Interface names given to
+    /// [`bind_device`](Ping::bind_device) and
+    /// [`scope_interface`](Ping::scope_interface) refer to the namespace's
+    /// interfaces.
+    ///
+    /// Only available on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn netns(&mut self, path: &'a Path) -> &mut Self {
+        self.netns = Some(path);
+        return self;
+    }
+
+    /// Takes the round-trip time from kernel timestamps instead of the
+    /// [`clock`](Ping::clock), which leaves out the time the process takes to
+    /// be scheduled and to handle the reply.
+    ///
+    /// The kernel stamps the reply when it arrives (`SO_TIMESTAMPNS`) and,
+    /// where supported, the request when it leaves (`SO_TIMESTAMPING`).
+    /// [`PingResult::timestamp_source`] tells which timestamps were used.
+    ///
+    /// Only available on Linux and Android.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn kernel_timestamps(&mut self, enabled: bool) -> &mut Self {
+        self.kernel_timestamps = enabled;
+        return self;
+    }
+
+    /// Forbids fragmenting the request on its way: sets the Don't Fragment
+    /// bit on IPv4, like `ping -M do`, and stops the kernel fragmenting it on
+    /// IPv6. A request larger than the path MTU then fails, usually with
+    /// [`Error::PacketTooBig`] or a local "message too long" error. See
+    /// [`path_mtu`](Ping::path_mtu).
+    ///
+    /// Fails when sending on platforms other than Linux, Android, macOS and
+    /// FreeBSD.
+    pub fn dont_fragment(&mut self, enabled: bool) -> &mut Self {
+        self.dont_fragment = enabled;
+        return self;
+    }
+
+    /// Ignores ICMPv4 replies and error messages whose ICMP or IPv4 header
+    /// checksum does not add up, as if they never arrived, instead of
+    /// returning the replies with [`PingResult::bad_checksum`] set. A request
+    /// whose only reply was damaged then times out.
+    ///
+    /// Off by default. IPv6 checksums are always verified, by the kernel.
+    pub fn strict_checksums(&mut self, enabled: bool) -> &mut Self {
+        self.strict_checksums = enabled;
+        return self;
+    }
+
+    /// Carries an IPv4 option in the request, to record the route or
+    /// timestamps of the hosts on the way; see [`IpOption`].
+    ///
+    /// The recorded data is only reported when the reply's IP header is
+    /// received, as on [`RAW`](SocketType::RAW) sockets. Ignored for IPv6
+    /// targets, and fails when sending on Windows.
+    pub fn ip_option(&mut self, ip_option: IpOption<'a>) -> &mut Self {
+        self.ip_option = Some(ip_option);
+        return self;
+    }
+
+    /// Sets how many requests [`iter`](Ping::iter) sends before it ends, like
+    /// `ping -c`.
+    ///
+    /// When unset, the iteration runs until the [`deadline`](Ping::deadline),
+    /// or forever.
+    pub fn count(&mut self, count: usize) -> &mut Self {
+        self.count = Some(count);
+        return self;
+    }
+
+    /// Sets the time between the start of two requests sent by
+    /// [`iter`](Ping::iter), like `ping -i`.
+    ///
+    /// Defaults to 1 second when unset. A request whose reply takes longer
+    /// than the interval delays the next one until the reply or timeout.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = Some(interval);
+        return self;
+    }
+
+    /// Sets an overall time limit for [`iter`](Ping::iter), counted from its
+    /// first request, like `ping -w`.
+    ///
+    /// No request is sent after the deadline, and the wait for the last reply
+    /// is cut short by it.
+    pub fn deadline(&mut self, deadline: Duration) -> &mut Self {
+        self.deadline = Some(deadline);
+        return self;
+    }
+
+    /// Sets the [`Clock`] used to measure round-trip times and timeouts.
+    ///
+    /// Defaults to [`MonotonicClock`], which is unaffected by changes to the
+    /// wall clock. Mostly useful to inject a fake clock in tests.
+    pub fn clock(&mut self, clock: &'a dyn Clock) -> &mut Self {
+        self.clock = Some(clock);
+        return self;
+    }
+
     /// Sends the echo request and blocks until a matching reply arrives or the
     /// timeout elapses.
     ///
@@ -384,13 +1280,21 @@ impl<'a> Ping<'a> {
     /// [`Error::IoError`] with kind
     /// [`ErrorKind::TimedOut`](std::io::ErrorKind::TimedOut).
     pub fn send(&self) -> Result<PingResult, Error> {
-        self.ping_with_socket(self.socket_type.into())
+        let request = self.request()?;
+        let socket = self.open_socket()?;
+        exchange(
+            &socket,
+            request,
+            self.timeout_or_default(),
+            self.clock_or_default(),
+        )
     }
 }
 
 /// Creates a [`Ping`] builder targeting `addr`.
 ///
 /// Shorthand for [`Ping::new`].
+#[allow(clippy::needless_return)]
 pub fn new<'a>(addr: IpAddr) -> Ping<'a> {
     return Ping::new(addr);
 }
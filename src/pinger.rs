@@ -0,0 +1,220 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use rand::random;
+use socket2::Socket;
+
+use crate::clock::Clock;
+use crate::errors::Error;
+use crate::ping::{Awaiting, Outstanding, Ping, PingResult, Request, exchange, receive_all};
+use crate::sys;
+
+/// A ping session that keeps its sockets open across many echo requests.
+///
+/// Create one with [`Ping::pinger`]. One socket per address family is opened,
+/// with the builder's [`SocketType`](crate::SocketType), the first time a
+/// target of that family is pinged and reused afterwards, so the ICMP
+/// identifier stays the same for the whole session, also on DGRAM sockets
+/// where the kernel picks it.
+///
+/// ```no_run
+/// let target = "8.8.8.8".parse().unwrap();
+/// let mut pinger = ping::new(target).pinger();
+/// for _ in 0..3 {
+///     let result = pinger.send().expect("ping failed");
+///     println!("seq={} rtt={:?}", result.seq_cnt, result.rtt);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Pinger<'a> {
+    pub(crate) ping: Ping<'a>,
+    v4: Option<Socket>,
+    v6: Option<Socket>,
+    seq_cnt: u16,
+}
+
+impl<'a> Pinger<'a> {
+    fn new(ping: &Ping<'a>) -> Self {
+        let mut ping = ping.clone();
+        let seq_cnt = ping.seq_cnt.unwrap_or(1);
+        ping.ident = Some(ping.ident.unwrap_or(random()));
+        Pinger {
+            ping,
+            v4: None,
+            v6: None,
+            seq_cnt,
+        }
+    }
+
+    /// The sequence number the next [`send`](Pinger::send) will use.
+    pub fn seq_cnt(&self) -> u16 {
+        self.seq_cnt
+    }
+
+    /// Sends the next echo request to the builder's target and blocks until
+    /// its reply arrives or the timeout elapses.
+    ///
+    /// Every call uses the next sequence number, wrapping around after
+    /// `u16::MAX`, and a fresh payload token unless one was set with
+    /// [`Ping::payload`]. Late replies to earlier requests are skipped. A
+    /// timeout is reported like in [`Ping::send`] and does not end the
+    /// session.
+    pub fn send(&mut self) -> Result<PingResult, Error> {
+        self.send_timeout(self.ping.timeout_or_default())
+    }
+
+    /// Like [`send`](Pinger::send), but waits at most `timeout` for the reply.
+    pub(crate) fn send_timeout(&mut self, timeout: Duration) -> Result<PingResult, Error> {
+        let clock = self.ping.clock_or_default();
+        let request = self.next_request(self.ping.addr)?;
+        let socket = self.socket(self.ping.addr)?;
+        exchange(socket, request, timeout, clock)
+    }
+
+    /// Sends one echo request to every target at once and waits for all of
+    /// the replies, fping style.
+    ///
+    /// IPv4 and IPv6 targets share the session's socket for their family, and
+    /// every request in the batch carries the same sequence number, the next
+    /// one of the session. Replies are matched to their target by source
+    /// address and payload token, and are received for both families in
+    /// parallel. The timeout applies to the whole batch.
+    ///
+    /// Returns one result per target, in the order of `targets`. The builder's
+    /// own target is not pinged.
+    ///
+    /// ```no_run
+    /// let targets = ["8.8.8.8".parse().unwrap(), "2001:4860:4860::8888".parse().unwrap()];
+    /// let mut pinger = ping::new(targets[0]).pinger();
+    /// for (target, result) in targets.iter().zip(pinger.send_many(&targets)) {
+    ///     match result {
+    ///         Ok(result) => println!("{target}: {:?}", result.rtt),
+    ///         Err(e) => println!("{target}: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn send_many(&mut self, targets: &[IpAddr]) -> Vec<Result<PingResult, Error>> {
+        let seq_cnt = self.next_seq_cnt();
+        let timeout = self.ping.timeout_or_default();
+        let clock = self.ping.clock_or_default();
+
+        let mut results: Vec<Option<Result<PingResult, Error>>> =
+            targets.iter().map(|_| None).collect();
+        let time_start = clock.now();
+        let mut awaiting_v4 = Awaiting::new(time_start, timeout, true);
+        let mut awaiting_v6 = Awaiting::new(time_start, timeout, true);
+
+        for (index, &target) in targets.iter().enumerate() {
+            let mut ping = self.ping.clone();
+            ping.addr = target;
+            ping.seq_cnt = Some(seq_cnt);
+            let awaiting = if target.is_ipv4() {
+                &mut awaiting_v4
+            } else {
+                &mut awaiting_v6
+            };
+            let sent = ping.request().and_then(|request| {
+                let socket = self.socket(target)?;
+                if awaiting.is_empty() {
+                    socket.set_write_timeout(Some(timeout))?;
+                    // Leave only the batch's transmit timestamps and ICMP
+                    // errors in the error queue.
+                    sys::drain_error_queue(socket);
+                }
+                let outstanding = Outstanding::new(index, request, clock.now());
+                socket.send_to(
+                    &outstanding.request.packet,
+                    &outstanding.request.dest.into(),
+                )?;
+                awaiting.push(outstanding);
+                Ok(())
+            });
+            if let Err(error) = sent {
+                results[index] = Some(Err(error));
+            }
+        }
+
+        let (v4, v6) = (&self.v4, &self.v6);
+        let received = std::thread::scope(|scope| {
+            let v6 = scope.spawn(|| collect(v6.as_ref(), awaiting_v6, clock));
+            let mut received = collect(v4.as_ref(), awaiting_v4, clock);
+            received.extend(v6.join().unwrap());
+            received
+        });
+        for (index, result) in received {
+            results[index] = Some(result);
+        }
+
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Changes the TTL of the session's requests to the builder's target,
+    /// opening the socket for its family if needed.
+    pub(crate) fn set_ttl(&mut self, ttl: u32) -> Result<(), Error> {
+        self.ping.ttl = Some(ttl);
+        let addr = self.ping.addr;
+        let socket = self.socket(addr)?;
+        if addr.is_ipv4() {
+            socket.set_ttl_v4(ttl)?;
+        } else {
+            socket.set_unicast_hops_v6(ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Takes the next sequence number of the session.
+    pub(crate) fn next_seq_cnt(&mut self) -> u16 {
+        let seq_cnt = self.seq_cnt;
+        self.seq_cnt = self.seq_cnt.wrapping_add(1);
+        seq_cnt
+    }
+
+    /// Encodes the next request of the session, to `addr`.
+    pub(crate) fn next_request(&mut self, addr: IpAddr) -> Result<Request, Error> {
+        let mut ping = self.ping.clone();
+        ping.addr = addr;
+        ping.seq_cnt = Some(self.next_seq_cnt());
+        ping.request()
+    }
+
+    /// The session's socket for the address family of `addr`, opened on first
+    /// use.
+    fn socket(&mut self, addr: IpAddr) -> Result<&Socket, Error> {
+        let socket = if addr.is_ipv4() {
+            &mut self.v4
+        } else {
+            &mut self.v6
+        };
+        if socket.is_none() {
+            let mut ping = self.ping.clone();
+            ping.addr = addr;
+            *socket = Some(ping.open_socket()?);
+        }
+        Ok(socket.as_ref().unwrap())
+    }
+}
+
+/// Receives the replies of a [`send_many`](Pinger::send_many) batch on
+/// `socket`, which is only missing when no request awaits a reply on it.
+fn collect(
+    socket: Option<&Socket>,
+    awaiting: Awaiting,
+    clock: &dyn Clock,
+) -> Vec<(usize, Result<PingResult, Error>)> {
+    match socket {
+        Some(socket) => receive_all(socket, awaiting, clock),
+        None => awaiting.into_timeouts().collect(),
+    }
+}
+
+impl<'a> Ping<'a> {
+    /// Starts a [`Pinger`] session with this builder's options.
+    ///
+    /// The session's first request uses the sequence number set with
+    /// [`Ping::seq_cnt`] (1 by default), and each request after that the next
+    /// one. When no identifier was set, one random identifier is chosen for
+    /// the whole session.
+    pub fn pinger(&self) -> Pinger<'a> {
+        Pinger::new(self)
+    }
+}
@@ -0,0 +1,109 @@
+use std::io;
+
+use crate::errors::Error;
+use crate::packet::encode_option;
+use crate::ping::Ping;
+
+/// The largest MTU probed for, the most an IP packet can hold without IPv6
+/// jumbograms.
+const MAX_MTU: usize = 65535;
+/// The smallest MTU every IPv4 link must support.
+const MIN_MTU_V4: usize = 68;
+/// The smallest MTU every IPv6 link must support.
+const MIN_MTU_V6: usize = 1280;
+/// The IP and ICMP headers in front of the payload of an echo request,
+/// without IPv4 options.
+const HEADERS_SIZE_V4: usize = 20 + 8;
+const HEADERS_SIZE_V6: usize = 40 + 8;
+
+#[cfg(unix)]
+const EMSGSIZE: i32 = libc::EMSGSIZE;
+#[cfg(windows)]
+const EMSGSIZE: i32 = 10040; // WSAEMSGSIZE
+
+impl<'a> Ping<'a> {
+    /// Discovers the path MTU to the target: the size of the largest IP
+    /// packet that reaches it without being fragmented.
+    ///
+    /// Echo requests are sent with [`dont_fragment`](Ping::dont_fragment) set
+    /// and a payload sized to fill a packet of the MTU being tried, and a
+    /// binary search narrows down the largest size that gets a reply. A
+    /// Fragmentation Needed or Packet Too Big message from a router makes the
+    /// size it reports the next one tried. A request refused locally, or that
+    /// gets no reply before the [`timeout`](Ping::timeout), counts as too big,
+    /// since routers may drop it silently; packet loss on the path can thus
+    /// make the result smaller than the real MTU.
+    ///
+    /// The requests are sent one after the other on a single socket, set up
+    /// from the builder as for any other ping, and the builder's
+    /// [`payload_size`](Ping::payload_size) is ignored. An
+    /// [`ip_option`](Ping::ip_option) takes its room from the payload. The
+    /// search is bounded by the smallest MTU of the address family (68 for
+    /// IPv4, 1280 for IPv6) and 65535. Fails with the error of the first
+    /// request, at the smallest MTU, if it gets no reply, or with any other
+    /// error that is not about the size of the request.
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let target = "8.8.8.8".parse().unwrap();
+    /// let mtu = ping::new(target)
+    ///     .timeout(Duration::from_secs(1))
+    ///     .path_mtu()
+    ///     .expect("path MTU discovery failed");
+    /// println!("path MTU: {mtu}");
+    /// ```
+    pub fn path_mtu(&self) -> Result<usize, Error> {
+        let mut ping = self.clone();
+        ping.dont_fragment(true);
+        let mut pinger = ping.pinger();
+        let (min_mtu, headers_size) = if self.addr.is_ipv4() {
+            // An IP option set with `ip_option` makes the header larger.
+            let options_size = self
+                .ip_option
+                .as_ref()
+                .and_then(encode_option)
+                .map_or(0, |option| option.len());
+            (MIN_MTU_V4, HEADERS_SIZE_V4 + options_size)
+        } else {
+            (MIN_MTU_V6, HEADERS_SIZE_V6)
+        };
+        let mut probe = |mtu: usize| {
+            pinger.ping.payload_size = Some(mtu - headers_size);
+            pinger.send()
+        };
+
+        probe(min_mtu)?;
+
+        // The largest MTU known to fit and the smallest known not to.
+        let (mut fits, mut too_big) = (min_mtu, MAX_MTU + 1);
+        let mut hint = None;
+        while too_big - fits > 1 {
+            let mtu = hint
+                .take()
+                .filter(|&mtu| fits < mtu && mtu < too_big)
+                .unwrap_or(fits + (too_big - fits) / 2);
+            match probe(mtu) {
+                Ok(_) => fits = mtu,
+                Err(Error::PacketTooBig { mtu: next_hop, .. }) => {
+                    too_big = mtu;
+                    hint = Some(next_hop as usize);
+                }
+                Err(error) if is_too_big(&error) => too_big = mtu,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(fits)
+    }
+}
+
+/// Whether a failed request is taken as too big for the path: refused by the
+/// local stack, or dropped without a word.
+fn is_too_big(error: &Error) -> bool {
+    match error {
+        Error::IoError { error } => {
+            error.kind() == io::ErrorKind::TimedOut || error.raw_os_error() == Some(EMSGSIZE)
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,133 @@
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use socket2::Socket;
+
+use crate::clock::Clock;
+use crate::errors::Error;
+use crate::iter::PingIter;
+use crate::ping::{Awaiting, Outstanding, Ping, PingResult, Request, timed_out};
+use crate::sys;
+
+/// The pieces of an async runtime needed to drive an ICMP socket.
+///
+/// Each supported runtime implements this once; the exchange itself lives in
+/// [`send`], which matches and times replies with the same [`Awaiting`] as the
+/// blocking [`Ping::send`].
+pub(crate) trait Runtime {
+    /// The socket registered with the runtime's reactor.
+    type Socket;
+
+    /// Registers a socket with the reactor, switching it to non-blocking mode.
+    fn register(socket: Socket) -> io::Result<Self::Socket>;
+
+    /// The registered socket itself.
+    fn get_ref(socket: &Self::Socket) -> &Socket;
+
+    /// Runs `op` once the socket is readable, retrying while it would block.
+    async fn read_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R>;
+
+    /// Runs `op` once the socket is writable, retrying while it would block.
+    async fn write_with<R>(
+        socket: &Self::Socket,
+        op: impl FnMut(&Socket) -> io::Result<R>,
+    ) -> io::Result<R>;
+
+    /// Resolves to `None` if `future` does not complete within `duration`.
+    async fn timeout<T>(duration: Duration, future: impl Future<Output = T>) -> Option<T>;
+
+    /// Waits for `duration` to pass.
+    async fn sleep(duration: Duration);
+
+    /// The iterator's socket registered with the reactor, kept across its
+    /// requests.
+    fn iter_socket<'i>(iter: &'i mut PingIter<'_>) -> &'i mut Option<Self::Socket>;
+}
+
+/// Sends the echo request described by `ping` and waits for its reply on the
+/// runtime `R`.
+///
+/// The returned future owns its socket, so dropping it closes the socket.
+pub(crate) async fn send<R: Runtime>(ping: &Ping<'_>) -> Result<PingResult, Error> {
+    let request = ping.request()?;
+    let socket = R::register(ping.open_socket()?)?;
+    exchange::<R>(
+        &socket,
+        request,
+        ping.timeout_or_default(),
+        ping.clock_or_default(),
+    )
+    .await
+}
+
+/// Sends `request` on `socket` and waits for its reply on the runtime `R`,
+/// like the blocking [`exchange`](crate::ping::exchange).
+async fn exchange<R: Runtime>(
+    socket: &R::Socket,
+    request: Request,
+    timeout: Duration,
+    clock: &dyn Clock,
+) -> Result<PingResult, Error> {
+    // Leave only this request's transmit timestamp and ICMP errors in the
+    // error queue.
+    sys::drain_error_queue(R::get_ref(socket));
+
+    let time_sent = clock.now();
+    let mut awaiting = Awaiting::new(time_sent, timeout, false);
+    let outstanding = Outstanding::new(0, request, time_sent);
+    let sent = R::write_with(socket, |socket| {
+        socket.send_to(
+            &outstanding.request.packet,
+            &outstanding.request.dest.into(),
+        )
+    });
+    match R::timeout(timeout, sent).await {
+        Some(sent) => sent?,
+        None => return Err(timed_out()),
+    };
+    awaiting.push(outstanding);
+
+    while let Some(remaining) = awaiting.remaining() {
+        let received = R::read_with(socket, |socket| sys::recv(socket, &mut awaiting.buffer));
+        let received = match R::timeout(remaining, received).await {
+            Some(received) => received?,
+            None => break,
+        };
+        if let Some((_, result)) = awaiting.receive(R::get_ref(socket), &received, clock) {
+            return result;
+        }
+    }
+    Err(timed_out())
+}
+
+/// Waits for the next request of `iter` to be due, then sends it on the
+/// runtime `R`, mirroring [`PingIter::next`](Iterator::next).
+///
+/// All the requests go out on one socket, opened on first use and kept by the
+/// iterator, so they share their ICMP identifier as in a
+/// [`Pinger`](crate::Pinger) session.
+pub(crate) async fn next<R: Runtime>(iter: &mut PingIter<'_>) -> Option<Result<PingResult, Error>> {
+    let (wait, timeout) = iter.schedule()?;
+    R::sleep(wait).await;
+    Some(send_next::<R>(iter, timeout).await)
+}
+
+/// Sends the next request of `iter` on its socket and waits at most `timeout`
+/// for the reply.
+async fn send_next<R: Runtime>(
+    iter: &mut PingIter<'_>,
+    timeout: Duration,
+) -> Result<PingResult, Error> {
+    let clock = iter.pinger.ping.clock_or_default();
+    let request = iter.pinger.next_request(iter.pinger.ping.addr)?;
+    if R::iter_socket(iter).is_none() {
+        let socket = R::register(iter.pinger.ping.open_socket()?)?;
+        *R::iter_socket(iter) = Some(socket);
+    }
+    let socket = R::iter_socket(iter).as_ref().unwrap();
+    exchange::<R>(socket, request, timeout, clock).await
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::ping::PingResult;
+
+/// Summary statistics over a series of pings, as printed by the `ping`
+/// command line tool when it exits.
+///
+/// Feed it every outcome, replies and losses alike, in the order the requests
+/// were sent:
+///
+/// ```no_run
+/// let target = "8.8.8.8".parse().unwrap();
+/// let mut stats = ping::PingStatistics::new();
+/// for result in ping::new(target).count(10).iter() {
+///     stats.record_result(&result);
+/// }
+/// println!("{stats}");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PingStatistics {
+    transmitted: usize,
+    received: usize,
+    corrupted: usize,
+    min: Option<Duration>,
+    max: Option<Duration>,
+    // Sums in nanoseconds, for the average and the mean deviation.
+    sum: f64,
+    sum_squares: f64,
+    last_rtt: Option<Duration>,
+    // Running interarrival jitter in nanoseconds.
+    jitter: f64,
+}
+
+impl PingStatistics {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a reply.
+    pub fn record(&mut self, result: &PingResult) {
+        self.record_rtt(result.rtt);
+        if result.corrupted || result.bad_checksum {
+            self.corrupted += 1;
+        }
+    }
+
+    /// Records a reply that took `rtt` to arrive.
+    pub fn record_rtt(&mut self, rtt: Duration) {
+        self.transmitted += 1;
+        self.received += 1;
+
+        self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+        self.max = Some(self.max.map_or(rtt, |max| max.max(rtt)));
+
+        let nanos = rtt.as_nanos() as f64;
+        self.sum += nanos;
+        self.sum_squares += nanos * nanos;
+
+        // RFC 3550, section 6.4.1: J(i) = J(i-1) + (|D(i-1,i)| - J(i-1)) / 16,
+        // where D is the difference between consecutive transit times.
+        if let Some(last_rtt) = self.last_rtt {
+            let difference = (nanos - last_rtt.as_nanos() as f64).abs();
+            self.jitter += (difference - self.jitter) / 16.0;
+        }
+        self.last_rtt = Some(rtt);
+    }
+
+    /// Records a request that got no reply, such as a timeout.
+    pub fn record_loss(&mut self) {
+        self.transmitted += 1;
+    }
+
+    /// Records the outcome of a ping: a reply on success, a loss on any error.
+    pub fn record_result(&mut self, result: &Result<PingResult, Error>) {
+        match result {
+            Ok(result) => self.record(result),
+            Err(_) => self.record_loss(),
+        }
+    }
+
+    /// The number of requests recorded.
+    pub fn transmitted(&self) -> usize {
+        self.transmitted
+    }
+
+    /// The number of replies recorded.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// The number of replies recorded that came back damaged, with a bad
+    /// checksum or altered padding. They also count as received.
+    pub fn corrupted(&self) -> usize {
+        self.corrupted
+    }
+
+    /// The percentage of requests that got no reply, from 0 to 100.
+    ///
+    /// 0 when nothing was recorded.
+    pub fn loss(&self) -> f64 {
+        if self.transmitted == 0 {
+            return 0.0;
+        }
+        let lost = self.transmitted - self.received;
+        100.0 * lost as f64 / self.transmitted as f64
+    }
+
+    /// The shortest round-trip time, or `None` without replies.
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    /// The mean round-trip time, or `None` without replies.
+    pub fn avg(&self) -> Option<Duration> {
+        self.mean().map(nanos_to_duration)
+    }
+
+    /// The longest round-trip time, or `None` without replies.
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    /// The standard deviation of the round-trip times, reported by `ping` as
+    /// `mdev`, or `None` without replies.
+    pub fn mdev(&self) -> Option<Duration> {
+        let mean = self.mean()?;
+        let mean_squares = self.sum_squares / self.received as f64;
+        Some(nanos_to_duration(
+            (mean_squares - mean * mean).max(0.0).sqrt(),
+        ))
+    }
+
+    /// The interarrival jitter as defined by RFC 3550: a running average of
+    /// the difference between consecutive round-trip times, smoothed over
+    /// about 16 replies. `None` until two replies were recorded.
+    pub fn jitter(&self) -> Option<Duration> {
+        if self.received < 2 {
+            return None;
+        }
+        Some(nanos_to_duration(self.jitter))
+    }
+
+    fn mean(&self) -> Option<f64> {
+        if self.received == 0 {
+            return None;
+        }
+        Some(self.sum / self.received as f64)
+    }
+}
+
+fn nanos_to_duration(nanos: f64) -> Duration {
+    Duration::from_nanos(nanos.round() as u64)
+}
+
+/// Formats the statistics like the summary of the `ping` command line tool.
+impl fmt::Display for PingStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} packets transmitted, {} received, ",
+            self.transmitted, self.received
+        )?;
+        if self.corrupted > 0 {
+            write!(f, "+{} corrupted, ", self.corrupted)?;
+        }
+        // Four decimals at most, like ping: 25%, 33.3333%.
+        let loss = format!("{:.4}", self.loss());
+        let loss = loss.trim_end_matches('0').trim_end_matches('.');
+        write!(f, "{loss}% packet loss")?;
+        if let (Some(min), Some(avg), Some(max), Some(mdev)) =
+            (self.min(), self.avg(), self.max(), self.mdev())
+        {
+            let millis = |rtt: Duration| rtt.as_secs_f64() * 1000.0;
+            write!(
+                f,
+                "\nrtt min/avg/max/mdev = {:.3}/{:.3}/{:.3}/{:.3} ms",
+                millis(min),
+                millis(avg),
+                millis(max),
+                millis(mdev)
+            )?;
+        }
+        Ok(())
+    }
+}
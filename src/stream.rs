@@ -0,0 +1,88 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::errors::Error;
+use crate::iter::PingIter;
+use crate::ping::PingResult;
+
+/// The future of the next item of a [`PingStream`], which hands the iterator
+/// back along with it.
+pub(crate) type Step<'a> =
+    Pin<Box<dyn Future<Output = (PingIter<'a>, Option<Result<PingResult, Error>>)> + Send + 'a>>;
+
+/// A [`PingIter`] driven asynchronously, as a [`Stream`] of its results.
+///
+/// Create one with `PingIter::into_stream` (with the `tokio` feature) or
+/// `PingIter::into_stream_io` (with the `async-io` feature). It yields the
+/// same items as the iterator, spaced by the same interval, so stream
+/// combinators, `select!` over streams and the like work on a series of
+/// pings. As with the iterator, all requests share one socket.
+///
+/// ```no_run
+/// # #[cfg(all(feature = "tokio", unix))]
+/// # async fn run() {
+/// use futures_lite::StreamExt;
+///
+/// let target = "8.8.8.8".parse().unwrap();
+/// let mut stream = ping::new(target).count(4).iter().into_stream();
+/// while let Some(result) = stream.next().await {
+///     match result {
+///         Ok(result) => println!("seq={} time={:?}", result.seq_cnt, result.rtt),
+///         Err(e) => println!("{e}"),
+///     }
+/// }
+/// # }
+/// ```
+pub struct PingStream<'a> {
+    /// The iterator, while no item is on its way.
+    iter: Option<PingIter<'a>>,
+    /// The future of the next item, which holds the iterator meanwhile.
+    step: Option<Step<'a>>,
+    /// Starts the future of the next item on the stream's runtime.
+    next: fn(PingIter<'a>) -> Step<'a>,
+}
+
+impl<'a> PingStream<'a> {
+    pub(crate) fn new(iter: PingIter<'a>, next: fn(PingIter<'a>) -> Step<'a>) -> Self {
+        PingStream {
+            iter: Some(iter),
+            step: None,
+            next,
+        }
+    }
+}
+
+impl std::fmt::Debug for PingStream<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PingStream")
+            .field("iter", &self.iter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Stream for PingStream<'_> {
+    type Item = Result<PingResult, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let step = match &mut this.step {
+            Some(step) => step,
+            None => match this.iter.take() {
+                Some(iter) => this.step.insert((this.next)(iter)),
+                None => return Poll::Ready(None),
+            },
+        };
+        let Poll::Ready((iter, item)) = step.as_mut().poll(cx) else {
+            return Poll::Pending;
+        };
+        this.step = None;
+        // Once the iterator is over, so is the stream.
+        if item.is_some() {
+            this.iter = Some(iter);
+        }
+        Poll::Ready(item)
+    }
+}
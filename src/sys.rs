@@ -0,0 +1,865 @@
+//! Socket I/O beyond what socket2 offers: reading the ancillary data the
+//! kernel attaches to received datagrams.
+
+use std::io;
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+use socket2::Socket;
+
+/// A datagram read from an ICMP socket.
+pub(crate) struct Received {
+    /// The number of bytes written to the buffer.
+    pub(crate) len: usize,
+    /// The sender of the datagram.
+    pub(crate) source: Option<IpAddr>,
+    /// When the kernel received the datagram, if receive timestamps are
+    /// enabled on the socket.
+    pub(crate) timestamp: Option<SystemTime>,
+    /// The TTL or hop limit the datagram arrived with, where the kernel
+    /// reports it; see [`enable_recv_ttl`].
+    pub(crate) ttl: Option<u8>,
+    /// The traffic class (IPv4 type of service) byte the datagram arrived
+    /// with, where the kernel reports it; see [`enable_recv_traffic_class`].
+    pub(crate) traffic_class: Option<u8>,
+    /// The address the datagram was sent to, a local one, where the kernel
+    /// reports it; see [`enable_recv_destination`].
+    pub(crate) destination: Option<IpAddr>,
+    /// A transmit timestamp taken off the error queue along the way. The
+    /// datagram is then empty.
+    pub(crate) transmitted: Option<SystemTime>,
+    /// Set when the datagram came from the error queue: the ICMP error
+    /// reported about a request. The buffer then holds the start of that
+    /// request's ICMP message, and `source` is its destination.
+    pub(crate) error: Option<QueuedError>,
+}
+
+/// An ICMP error the kernel queued for the socket; see `IP_RECVERR` in
+/// `ip(7)`.
+pub(crate) struct QueuedError {
+    pub(crate) icmp_type: u8,
+    pub(crate) code: u8,
+    /// The MTU for Fragmentation Needed and Packet Too Big.
+    pub(crate) info: u32,
+    /// The router or host that sent the error.
+    pub(crate) offender: Option<IpAddr>,
+}
+
+/// Receives one datagram into `buffer`.
+///
+/// On Linux, entries of the socket's error queue are returned first, one per
+/// call.
+#[cfg(unix)]
+pub(crate) fn recv(socket: &Socket, buffer: &mut [u8]) -> io::Result<Received> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(received) = linux::recv_error_queue(socket, buffer)? {
+        return Ok(received);
+    }
+
+    // Receive timestamps are only read on Linux.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let mut timestamp = None;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let timestamp = None;
+    let mut ttl = None;
+    let mut traffic_class = None;
+    let mut destination = None;
+    #[allow(unused_variables)]
+    let received = unix::recvmsg(socket, buffer, 0, |level, kind, data| {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if let Some(time) = linux::timestamp(level, kind, data) {
+            timestamp = Some(time);
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple",
+            target_os = "freebsd",
+            target_os = "dragonfly"
+        ))]
+        if let Some(value) = unix::ttl(level, kind, data) {
+            ttl = Some(value);
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple",
+            target_os = "freebsd"
+        ))]
+        if let Some(value) = unix::traffic_class(level, kind, data) {
+            traffic_class = Some(value);
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple",
+            target_os = "freebsd"
+        ))]
+        if let Some(addr) = unix::destination(level, kind, data) {
+            destination = Some(addr);
+        }
+    });
+
+    // With IP_RECVERR, an ICMP error also fails the next read once. Its entry
+    // may already have been taken off the queue by an earlier call, in which
+    // case there is nothing to report.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let received = match received {
+        Err(error) if linux::is_icmp_error(&error) => {
+            return Ok(
+                linux::recv_error_queue(socket, buffer)?.unwrap_or(Received {
+                    len: 0,
+                    source: None,
+                    timestamp: None,
+                    ttl: None,
+                    traffic_class: None,
+                    destination: None,
+                    transmitted: None,
+                    error: None,
+                }),
+            );
+        }
+        received => received,
+    };
+
+    let (len, source) = received?;
+    Ok(Received {
+        len,
+        source,
+        timestamp,
+        ttl,
+        traffic_class,
+        destination,
+        transmitted: None,
+        error: None,
+    })
+}
+
+/// Receives one datagram into `buffer`.
+#[cfg(not(unix))]
+pub(crate) fn recv(socket: &Socket, buffer: &mut [u8]) -> io::Result<Received> {
+    // socket2 0.6 recv_from requires &mut [MaybeUninit<u8>]; cast is sound
+    // because MaybeUninit<u8> has the same layout as u8.
+    let (len, src_addr) = socket.recv_from(unsafe {
+        std::slice::from_raw_parts_mut(
+            buffer.as_mut_ptr() as *mut std::mem::MaybeUninit<u8>,
+            buffer.len(),
+        )
+    })?;
+    Ok(Received {
+        len,
+        source: src_addr.as_socket().map(|s| s.ip()),
+        timestamp: None,
+        ttl: None,
+        traffic_class: None,
+        destination: None,
+        transmitted: None,
+        error: None,
+    })
+}
+
+/// Asks the kernel to report the TTL or hop limit of received datagrams, so
+/// it is known even when the IP header is not part of the datagram.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+))]
+pub(crate) fn enable_recv_ttl(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_RECVHOPLIMIT,
+            1 as libc::c_int,
+        )
+    } else {
+        unix::setsockopt(socket, libc::IPPROTO_IP, libc::IP_RECVTTL, 1 as libc::c_int)
+    }
+}
+
+/// Reporting the TTL of received datagrams is not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+)))]
+pub(crate) fn enable_recv_ttl(_socket: &Socket, _ipv6: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Sets the traffic class (IPv4 type of service) byte of outgoing datagrams.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+pub(crate) fn set_traffic_class(socket: &Socket, ipv6: bool, value: u8) -> io::Result<()> {
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_TCLASS,
+            libc::c_int::from(value),
+        )
+    } else {
+        socket.set_tos_v4(u32::from(value))
+    }
+}
+
+/// Sets the type of service byte of outgoing datagrams; the IPv6 traffic
+/// class is not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+pub(crate) fn set_traffic_class(socket: &Socket, ipv6: bool, value: u8) -> io::Result<()> {
+    if ipv6 {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the IPv6 traffic class is not supported on this platform",
+        ));
+    }
+    socket.set_tos_v4(u32::from(value))
+}
+
+/// Asks the kernel to report the traffic class (IPv4 type of service) byte
+/// of received datagrams, so it is known even when the IP header is not part
+/// of the datagram.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+pub(crate) fn enable_recv_traffic_class(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_RECVTCLASS,
+            1 as libc::c_int,
+        )
+    } else {
+        unix::setsockopt(socket, libc::IPPROTO_IP, libc::IP_RECVTOS, 1 as libc::c_int)
+    }
+}
+
+/// Reporting the traffic class of received datagrams is not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+pub(crate) fn enable_recv_traffic_class(_socket: &Socket, _ipv6: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Asks the kernel to report the destination address of received datagrams,
+/// which tells the local address a reply was sent to.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+pub(crate) fn enable_recv_destination(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_RECVPKTINFO,
+            1 as libc::c_int,
+        )
+    } else {
+        #[cfg(not(target_os = "freebsd"))]
+        let option = libc::IP_PKTINFO;
+        #[cfg(target_os = "freebsd")]
+        let option = libc::IP_RECVDSTADDR;
+        unix::setsockopt(socket, libc::IPPROTO_IP, option, 1 as libc::c_int)
+    }
+}
+
+/// Reporting the destination of received datagrams is not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+pub(crate) fn enable_recv_destination(_socket: &Socket, _ipv6: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// Forbids fragmenting outgoing requests: the Don't Fragment bit on IPv4, and
+/// no fragmentation by the sender on IPv6. A request larger than the path MTU
+/// is then refused, locally or by a router on the way, instead of being split.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_dont_fragment(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    // The probe mode sets the bit but ignores the path MTU the kernel has
+    // cached, so every request really travels the path.
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_MTU_DISCOVER,
+            libc::IPV6_PMTUDISC_PROBE,
+        )?;
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_DONTFRAG,
+            1 as libc::c_int,
+        )
+    } else {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            libc::IP_PMTUDISC_PROBE,
+        )
+    }
+}
+
+/// Forbids fragmenting outgoing requests: the Don't Fragment bit on IPv4, and
+/// no fragmentation by the sender on IPv6.
+#[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+pub(crate) fn set_dont_fragment(socket: &Socket, ipv6: bool) -> io::Result<()> {
+    if ipv6 {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_DONTFRAG,
+            1 as libc::c_int,
+        )
+    } else {
+        unix::setsockopt(
+            socket,
+            libc::IPPROTO_IP,
+            libc::IP_DONTFRAG,
+            1 as libc::c_int,
+        )
+    }
+}
+
+/// Forbidding fragmentation is not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+pub(crate) fn set_dont_fragment(_socket: &Socket, _ipv6: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "don't fragment is not supported on this platform",
+    ))
+}
+
+/// Looks up the index of the network interface called `name`.
+#[cfg(unix)]
+pub(crate) fn interface_index(name: &str) -> io::Result<u32> {
+    let name = std::ffi::CString::new(name)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))?;
+    // SAFETY: `name` is a valid NUL-terminated string.
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(index)
+}
+
+/// Looking up interfaces by name is not supported here.
+#[cfg(not(unix))]
+pub(crate) fn interface_index(_name: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "interface names are not supported on this platform",
+    ))
+}
+
+/// Sets the IPv4 options of outgoing datagrams, encoded as in the header.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+pub(crate) fn set_ip_options(socket: &Socket, options: &[u8]) -> io::Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let option = libc::IP_OPTIONS;
+    // Not exported by libc on these, but part of the BSD socket API.
+    #[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+    let option: libc::c_int = 1;
+    unix::setsockopt_bytes(socket, libc::IPPROTO_IP, option, options)
+}
+
+/// IP options are not supported here.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+)))]
+pub(crate) fn set_ip_options(_socket: &Socket, _options: &[u8]) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "IP options are not supported on this platform",
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use self::linux::{
+    drain_error_queue, enable_recv_errors, enable_timestamps, in_netns, transmit_timestamp,
+};
+
+/// Kernel timestamps are only supported on Linux.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn transmit_timestamp(_socket: &Socket) -> Option<SystemTime> {
+    None
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn drain_error_queue(_socket: &Socket) {}
+
+#[cfg(unix)]
+mod unix {
+    use std::io;
+    use std::mem;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::os::fd::AsRawFd;
+
+    use libc::c_int;
+    use socket2::Socket;
+
+    /// Room for the control messages we enable; `u64` keeps it aligned for
+    /// `cmsghdr`.
+    type ControlBuffer = [u64; 64];
+
+    /// Sets a socket option of any plain value type.
+    pub(crate) fn setsockopt<T>(
+        socket: &Socket,
+        level: c_int,
+        name: c_int,
+        value: T,
+    ) -> io::Result<()> {
+        // SAFETY: `value` lives for the duration of the call and its size is
+        // passed along with it.
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                (&value as *const T).cast(),
+                mem::size_of::<T>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Calls `setsockopt(2)` with a value of variable size.
+    pub(crate) fn setsockopt_bytes(
+        socket: &Socket,
+        level: c_int,
+        name: c_int,
+        value: &[u8],
+    ) -> io::Result<()> {
+        // SAFETY: `value` is valid for its length, which is passed along.
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                value.as_ptr().cast(),
+                value.len() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Calls `recvmsg(2)`, handing every control message to `on_cmsg` as its
+    /// level, type and data. Returns the datagram length and its sender.
+    pub(crate) fn recvmsg(
+        socket: &Socket,
+        buffer: &mut [u8],
+        flags: c_int,
+        mut on_cmsg: impl FnMut(c_int, c_int, &[u8]),
+    ) -> io::Result<(usize, Option<IpAddr>)> {
+        // SAFETY: all-zero is a valid value for these plain C structs.
+        let mut name: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut control: ControlBuffer = [0; 64];
+        let mut iov = libc::iovec {
+            iov_base: buffer.as_mut_ptr().cast(),
+            iov_len: buffer.len(),
+        };
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_name = (&mut name as *mut libc::sockaddr_storage).cast();
+        msg.msg_namelen = mem::size_of_val(&name) as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = mem::size_of_val(&control) as _;
+
+        // SAFETY: every pointer in `msg` refers to a live buffer of the
+        // advertised length.
+        let len = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, flags) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the kernel filled in `msg`, and the CMSG macros stay within
+        // its `msg_controllen`.
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let data = libc::CMSG_DATA(cmsg);
+                let header_len = data as usize - cmsg as usize;
+                let data_len = ((*cmsg).cmsg_len as usize).saturating_sub(header_len);
+                on_cmsg(
+                    (*cmsg).cmsg_level,
+                    (*cmsg).cmsg_type,
+                    std::slice::from_raw_parts(data, data_len),
+                );
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        Ok((len as usize, sockaddr_ip(&name)))
+    }
+
+    /// Extracts the TTL or hop limit from an `IP_TTL`/`IP_RECVTTL` or
+    /// `IPV6_HOPLIMIT` control message. Linux sends the IPv4 TTL as an `int`,
+    /// the BSDs as a single byte.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_vendor = "apple",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    pub(crate) fn ttl(level: c_int, kind: c_int, data: &[u8]) -> Option<u8> {
+        let is_ttl = match level {
+            libc::IPPROTO_IP => kind == libc::IP_TTL || kind == libc::IP_RECVTTL,
+            libc::IPPROTO_IPV6 => kind == libc::IPV6_HOPLIMIT,
+            _ => false,
+        };
+        if !is_ttl {
+            return None;
+        }
+        byte_or_int(data)
+    }
+
+    /// Reads the traffic class (IPv4 type of service) from a control message,
+    /// if it is one.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_vendor = "apple",
+        target_os = "freebsd"
+    ))]
+    pub(crate) fn traffic_class(level: c_int, kind: c_int, data: &[u8]) -> Option<u8> {
+        // Linux reports the type of service as IP_TOS, the BSDs as
+        // IP_RECVTOS.
+        let is_traffic_class = match level {
+            libc::IPPROTO_IP => kind == libc::IP_TOS || kind == libc::IP_RECVTOS,
+            libc::IPPROTO_IPV6 => kind == libc::IPV6_TCLASS,
+            _ => false,
+        };
+        if !is_traffic_class {
+            return None;
+        }
+        byte_or_int(data)
+    }
+
+    /// Reads the destination address of the datagram from a control message,
+    /// if it is one.
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_vendor = "apple",
+        target_os = "freebsd"
+    ))]
+    pub(crate) fn destination(level: c_int, kind: c_int, data: &[u8]) -> Option<IpAddr> {
+        // SAFETY: the length of the data is checked before it is read as the
+        // struct its type announces.
+        match (level, kind) {
+            #[cfg(not(target_os = "freebsd"))]
+            (libc::IPPROTO_IP, libc::IP_PKTINFO)
+                if data.len() >= mem::size_of::<libc::in_pktinfo>() =>
+            {
+                let info: libc::in_pktinfo =
+                    unsafe { std::ptr::read_unaligned(data.as_ptr().cast()) };
+                Some(Ipv4Addr::from(u32::from_be(info.ipi_addr.s_addr)).into())
+            }
+            #[cfg(target_os = "freebsd")]
+            (libc::IPPROTO_IP, libc::IP_RECVDSTADDR)
+                if data.len() >= mem::size_of::<libc::in_addr>() =>
+            {
+                let addr: libc::in_addr = unsafe { std::ptr::read_unaligned(data.as_ptr().cast()) };
+                Some(Ipv4Addr::from(u32::from_be(addr.s_addr)).into())
+            }
+            (libc::IPPROTO_IPV6, libc::IPV6_PKTINFO)
+                if data.len() >= mem::size_of::<libc::in6_pktinfo>() =>
+            {
+                let info: libc::in6_pktinfo =
+                    unsafe { std::ptr::read_unaligned(data.as_ptr().cast()) };
+                Some(Ipv6Addr::from(info.ipi6_addr.s6_addr).into())
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a control message value that is a byte on some platforms and an
+    /// `int` on others.
+    fn byte_or_int(data: &[u8]) -> Option<u8> {
+        match data.len() {
+            1 => Some(data[0]),
+            // SAFETY: the length was checked.
+            len if len >= mem::size_of::<c_int>() => {
+                let value = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const c_int) };
+                u8::try_from(value).ok()
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn sockaddr_ip(name: &libc::sockaddr_storage) -> Option<IpAddr> {
+        // SAFETY: `ss_family` tells which struct the storage holds, and
+        // `sockaddr_storage` is large and aligned enough for all of them.
+        match name.ss_family as c_int {
+            libc::AF_INET => {
+                let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in) };
+                Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into())
+            }
+            libc::AF_INET6 => {
+                let addr = unsafe { &*(name as *const _ as *const libc::sockaddr_in6) };
+                Some(Ipv6Addr::from(addr.sin6_addr.s6_addr).into())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod linux {
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    use libc::c_int;
+    use socket2::Socket;
+
+    use super::unix::{recvmsg, setsockopt, sockaddr_ip};
+    use super::{QueuedError, Received};
+
+    /// Runs `f` on a helper thread that joined the network namespace at
+    /// `path`, such as `/var/run/netns/foo`. Sockets keep the namespace they
+    /// were created in, so one created by `f` can be used from any thread.
+    pub(crate) fn in_netns<T: Send>(
+        path: &Path,
+        f: impl FnOnce() -> io::Result<T> + Send,
+    ) -> io::Result<T> {
+        let namespace = File::open(path)?;
+        std::thread::scope(|scope| {
+            let helper = scope.spawn(|| {
+                // The thread ends right after, so it never switches back.
+                // SAFETY: `namespace` is an open file descriptor.
+                let ret = unsafe { libc::setns(namespace.as_raw_fd(), libc::CLONE_NEWNET) };
+                if ret < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                f()
+            });
+            helper
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    /// Asks the kernel to queue ICMP errors about sent requests on the
+    /// socket's error queue, where [`recv`](super::recv) picks them up.
+    /// Datagram sockets get them no other way.
+    pub(crate) fn enable_recv_errors(socket: &Socket, ipv6: bool) -> io::Result<()> {
+        if ipv6 {
+            setsockopt(socket, libc::SOL_IPV6, libc::IPV6_RECVERR, 1 as c_int)
+        } else {
+            setsockopt(socket, libc::SOL_IP, libc::IP_RECVERR, 1 as c_int)
+        }
+    }
+
+    /// Whether `error` is how a read reports an ICMP error with IP_RECVERR
+    /// enabled; these are the errnos ICMP errors are converted to.
+    pub(crate) fn is_icmp_error(error: &io::Error) -> bool {
+        matches!(
+            error.raw_os_error(),
+            Some(
+                libc::ENETUNREACH
+                    | libc::EHOSTUNREACH
+                    | libc::ENONET
+                    | libc::ECONNREFUSED
+                    | libc::ENOPROTOOPT
+                    | libc::EMSGSIZE
+                    | libc::EOPNOTSUPP
+                    | libc::EACCES
+                    | libc::EHOSTDOWN
+                    | libc::EPROTO
+            )
+        )
+    }
+
+    /// Takes one entry off the socket's error queue without blocking, or
+    /// returns `None` if it is empty.
+    ///
+    /// An ICMP error comes back with the start of the request it is about;
+    /// any other entry comes back empty, carrying its transmit timestamp if
+    /// it is one.
+    pub(crate) fn recv_error_queue(
+        socket: &Socket,
+        buffer: &mut [u8],
+    ) -> io::Result<Option<Received>> {
+        let mut error = None;
+        let mut transmitted = None;
+        let flags = libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT;
+        let received = recvmsg(socket, buffer, flags, |level, kind, data| {
+            if kind == libc::SCM_TIMESTAMPING {
+                transmitted = transmitted.or(timestamp(level, kind, data));
+            } else if (level, kind) == (libc::SOL_IP, libc::IP_RECVERR)
+                || (level, kind) == (libc::SOL_IPV6, libc::IPV6_RECVERR)
+            {
+                error = queued_error(data);
+            }
+        });
+        let (len, source) = match received {
+            Ok(received) => received,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        Ok(Some(Received {
+            len: if error.is_some() { len } else { 0 },
+            source,
+            timestamp: None,
+            ttl: None,
+            traffic_class: None,
+            destination: None,
+            transmitted,
+            error,
+        }))
+    }
+
+    /// Decodes a `sock_extended_err` that came from an ICMP error, along
+    /// with the address of its sender that follows it.
+    fn queued_error(data: &[u8]) -> Option<QueuedError> {
+        let size = std::mem::size_of::<libc::sock_extended_err>();
+        if data.len() < size {
+            return None;
+        }
+        // SAFETY: the length was checked above.
+        let error =
+            unsafe { std::ptr::read_unaligned(data.as_ptr() as *const libc::sock_extended_err) };
+        if error.ee_origin != libc::SO_EE_ORIGIN_ICMP && error.ee_origin != libc::SO_EE_ORIGIN_ICMP6
+        {
+            return None;
+        }
+
+        // SAFETY: all-zero is a valid `sockaddr_storage`, and the copy stays
+        // within both it and `data`.
+        let mut offender: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let offender_len = (data.len() - size).min(std::mem::size_of_val(&offender));
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data[size..].as_ptr(),
+                (&mut offender as *mut libc::sockaddr_storage).cast(),
+                offender_len,
+            );
+        }
+
+        Some(QueuedError {
+            icmp_type: error.ee_type,
+            code: error.ee_code,
+            info: error.ee_info,
+            offender: sockaddr_ip(&offender),
+        })
+    }
+
+    /// Asks the kernel to timestamp received datagrams and, where supported,
+    /// sent ones, in software.
+    ///
+    /// Receive timestamps use `SO_TIMESTAMPNS`, which also stamps datagrams
+    /// that arrived before timestamping was switched on system-wide. Transmit
+    /// timestamps use `SO_TIMESTAMPING` and are skipped where it is missing.
+    pub(crate) fn enable_timestamps(socket: &Socket) -> io::Result<()> {
+        setsockopt(socket, libc::SOL_SOCKET, libc::SO_TIMESTAMPNS, 1 as c_int)?;
+        let flags = libc::SOF_TIMESTAMPING_TX_SOFTWARE
+            | libc::SOF_TIMESTAMPING_SOFTWARE
+            | libc::SOF_TIMESTAMPING_OPT_TSONLY;
+        let _ = setsockopt(socket, libc::SOL_SOCKET, libc::SO_TIMESTAMPING, flags);
+        Ok(())
+    }
+
+    /// Extracts the software timestamp from a `SCM_TIMESTAMPING` or
+    /// `SCM_TIMESTAMPNS` control message.
+    pub(crate) fn timestamp(level: c_int, kind: c_int, data: &[u8]) -> Option<SystemTime> {
+        if level != libc::SOL_SOCKET
+            || (kind != libc::SCM_TIMESTAMPING && kind != libc::SCM_TIMESTAMPNS)
+            || data.len() < std::mem::size_of::<libc::timespec>()
+        {
+            return None;
+        }
+        // Both start with a timespec; for SCM_TIMESTAMPING it is the software
+        // one. An all-zero value means the kernel had none to report.
+        // SAFETY: the length was checked above.
+        let time = unsafe { std::ptr::read_unaligned(data.as_ptr() as *const libc::timespec) };
+        if time.tv_sec == 0 && time.tv_nsec == 0 {
+            return None;
+        }
+        let since_epoch = Duration::new(time.tv_sec as u64, time.tv_nsec as u32);
+        Some(SystemTime::UNIX_EPOCH + since_epoch)
+    }
+
+    /// Reads the transmit timestamp of the last datagram sent from the
+    /// socket's error queue, without blocking.
+    pub(crate) fn transmit_timestamp(socket: &Socket) -> Option<SystemTime> {
+        let mut buffer = [0; 64];
+        loop {
+            let mut transmitted = None;
+            recvmsg(
+                socket,
+                &mut buffer,
+                libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT,
+                |level, kind, data| {
+                    if kind == libc::SCM_TIMESTAMPING {
+                        transmitted = transmitted.or(timestamp(level, kind, data));
+                    }
+                },
+            )
+            .ok()?;
+            if transmitted.is_some() {
+                return transmitted;
+            }
+        }
+    }
+
+    /// Discards everything left in the socket's error queue, such as transmit
+    /// timestamps of earlier requests.
+    pub(crate) fn drain_error_queue(socket: &Socket) {
+        let mut buffer = [0; 64];
+        let flags = libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT;
+        while recvmsg(socket, &mut buffer, flags, |_, _, _| {}).is_ok() {}
+    }
+}
@@ -0,0 +1,163 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::errors::Error;
+use crate::ping::Ping;
+use crate::pinger::Pinger;
+
+pub(crate) const DEFAULT_MAX_HOPS: u32 = 30;
+const DEFAULT_PROBES_PER_HOP: usize = 3;
+
+/// An ICMP traceroute, discovering the routers on the path to the target.
+///
+/// Create one with [`Ping::traceroute`]. It sends echo requests with
+/// increasing TTLs (hop limits on IPv6), starting at 1, and yields one [`Hop`]
+/// per TTL with the routers that reported the TTL running out. It stops after
+/// the hop where the target itself replies, where a probe fails with anything
+/// but a timeout (such as [`Error::DestinationUnreachable`]), or after
+/// [`max_hops`](Traceroute::max_hops).
+///
+/// The probes are sent one after the other on a single socket, as in a
+/// [`Pinger`] session, each waiting for its answer up to the builder's
+/// [`timeout`](Ping::timeout). The builder's own [`ttl`](Ping::ttl) is
+/// ignored.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let target = "8.8.8.8".parse().unwrap();
+/// for hop in ping::new(target).timeout(Duration::from_secs(1)).traceroute().max_hops(20) {
+///     print!("{:2}", hop.ttl);
+///     for probe in &hop.probes {
+///         match probe {
+///             Ok(probe) => print!("  {} {:?}", probe.addr, probe.rtt),
+///             Err(_) => print!("  *"),
+///         }
+///     }
+///     println!();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Traceroute<'a> {
+    pinger: Pinger<'a>,
+    max_hops: u32,
+    probes_per_hop: usize,
+    ttl: u32,
+    done: bool,
+}
+
+/// The outcome of the probes sent with one TTL, yielded by [`Traceroute`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Hop {
+    /// The TTL the probes were sent with; the first hop is 1.
+    pub ttl: u32,
+    /// One outcome per probe, in the order they were sent. A probe nobody
+    /// answered in time fails with the usual timeout error.
+    pub probes: Vec<Result<Probe, Error>>,
+}
+
+impl Hop {
+    /// Whether the target itself answered a probe of this hop.
+    pub fn reached(&self) -> bool {
+        self.probes.iter().flatten().any(|probe| probe.reached)
+    }
+}
+
+/// An answered traceroute probe.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct Probe {
+    /// The router that reported the TTL running out, or the target.
+    pub addr: IpAddr,
+    /// The round-trip time of the probe.
+    pub rtt: Duration,
+    /// Whether the answer is an echo reply from the target, rather than a
+    /// Time Exceeded message from a router on the way.
+    pub reached: bool,
+}
+
+#[allow(clippy::needless_return)]
+impl<'a> Traceroute<'a> {
+    fn new(ping: &Ping<'a>) -> Self {
+        Traceroute {
+            pinger: ping.pinger(),
+            max_hops: DEFAULT_MAX_HOPS,
+            probes_per_hop: DEFAULT_PROBES_PER_HOP,
+            ttl: 1,
+            done: false,
+        }
+    }
+
+    /// Sets the largest TTL to probe with. Defaults to 30.
+    pub fn max_hops(&mut self, max_hops: u32) -> &mut Self {
+        self.max_hops = max_hops;
+        return self;
+    }
+
+    /// Sets how many probes are sent per hop. Defaults to 3.
+    pub fn probes_per_hop(&mut self, probes_per_hop: usize) -> &mut Self {
+        self.probes_per_hop = probes_per_hop;
+        return self;
+    }
+}
+
+/// Sends one probe with the session's current TTL.
+pub(crate) fn probe(pinger: &mut Pinger) -> Result<Probe, Error> {
+    let clock = pinger.ping.clock_or_default();
+    let time_start = clock.now();
+    match pinger.send() {
+        Ok(result) => Ok(Probe {
+            addr: result.source,
+            rtt: result.rtt,
+            reached: true,
+        }),
+        // Errors carry no round-trip time, so time the whole send.
+        Err(Error::TimeExceeded { reporter, code: 0 }) => Ok(Probe {
+            addr: reporter,
+            rtt: clock.now().saturating_duration_since(time_start),
+            reached: false,
+        }),
+        Err(error) => Err(error),
+    }
+}
+
+/// Whether no probe with a larger TTL is worth sending after this outcome:
+/// the target answered, or the probe failed with anything but a timeout.
+pub(crate) fn is_last_hop(probe: &Result<Probe, Error>) -> bool {
+    match probe {
+        Ok(probe) => probe.reached,
+        Err(Error::IoError { error }) => error.kind() != std::io::ErrorKind::TimedOut,
+        Err(_) => true,
+    }
+}
+
+impl Iterator for Traceroute<'_> {
+    type Item = Hop;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.ttl > self.max_hops {
+            return None;
+        }
+
+        let ttl = self.ttl;
+        self.ttl += 1;
+        let probes: Vec<_> = match self.pinger.set_ttl(ttl) {
+            Ok(()) => (0..self.probes_per_hop)
+                .map(|_| probe(&mut self.pinger))
+                .collect(),
+            Err(error) => vec![Err(error)],
+        };
+
+        self.done = probes.iter().any(is_last_hop);
+        Some(Hop { ttl, probes })
+    }
+}
+
+impl<'a> Ping<'a> {
+    /// Returns an iterator over the hops on the path to the target, like the
+    /// `traceroute` command line tool. See [`Traceroute`].
+    pub fn traceroute(&self) -> Traceroute<'a> {
+        Traceroute::new(self)
+    }
+}
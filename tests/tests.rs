@@ -1,6 +1,7 @@
 use rand::random;
 use socket2::{Domain, Protocol, Socket, Type};
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 macro_rules! skip_if_no_capability {
     () => {
@@ -126,6 +127,99 @@ fn bind_device() {
         .unwrap();
 }
 
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mark() {
+    skip_if_no_capability!();
+    let result = ping::new("127.0.0.1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .mark(42)
+        .send();
+    if unsafe { libc::geteuid() } == 0 {
+        result.unwrap();
+    } else {
+        assert!(
+            matches!(result, Err(ping::Error::MarkNotPermitted)),
+            "expected the mark to be refused, got {result:?}"
+        );
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn netns() {
+    skip_if_not_root!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+
+    // Our own namespace behaves as if none was given.
+    let path = std::path::Path::new("/proc/self/ns/net");
+    let mut pinger = ping::new(addr)
+        .socket_type(ping::RAW)
+        .timeout(timeout)
+        .netns(path)
+        .pinger();
+    pinger.send().unwrap();
+    pinger.send().unwrap();
+
+    // A fresh namespace, held by a thread, has its loopback interface down.
+    let (tid_sender, tid) = std::sync::mpsc::channel();
+    let (done, wait) = std::sync::mpsc::channel::<()>();
+    let holder = std::thread::spawn(move || {
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } < 0 {
+            tid_sender.send(None).unwrap();
+            return;
+        }
+        tid_sender
+            .send(Some(unsafe { libc::syscall(libc::SYS_gettid) }))
+            .unwrap();
+        let _ = wait.recv();
+    });
+    let Some(tid) = tid.recv().unwrap() else {
+        eprintln!("Skipping test: cannot create a network namespace");
+        return;
+    };
+    let path = format!("/proc/self/task/{tid}/ns/net");
+    let result = ping::new(addr)
+        .socket_type(ping::RAW)
+        .timeout(timeout)
+        .netns(std::path::Path::new(&path))
+        .send();
+    // Nor does it have the interfaces of ours besides loopback.
+    let interface = std::fs::read_dir("/sys/class/net")
+        .unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .find(|name| name != "lo");
+    let scoped_result = interface.as_ref().map(|interface| {
+        ping::new("::1".parse().unwrap())
+            .socket_type(ping::RAW)
+            .timeout(timeout)
+            .scope_interface(interface)
+            .netns(std::path::Path::new(&path))
+            .send()
+    });
+    drop(done);
+    holder.join().unwrap();
+    match result {
+        Err(ping::Error::IoError { error }) => {
+            assert_eq!(error.kind(), std::io::ErrorKind::NetworkUnreachable)
+        }
+        result => panic!("expected no route, got {result:?}"),
+    }
+    match scoped_result {
+        Some(Err(ping::Error::IoError { error })) => {
+            assert_eq!(error.raw_os_error(), Some(libc::ENODEV))
+        }
+        None => {}
+        Some(result) => panic!("expected no such interface, got {result:?}"),
+    }
+
+    let result = ping::new(addr)
+        .netns(std::path::Path::new("/var/run/netns/no-such-namespace"))
+        .send();
+    assert!(result.is_err());
+}
+
 #[test]
 fn duration() {
     // Ensure that the duration returned is less than the rtt
@@ -174,6 +268,468 @@ fn ping_result_fields() {
     assert!(result.payload.starts_with(&custom_payload));
 }
 
+#[test]
+fn payload_size() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let token = [7; 24];
+    let result = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .payload(&token)
+        .payload_size(1472)
+        .send()
+        .unwrap();
+    assert_eq!(result.payload.len(), 1472);
+    assert!(result.payload.starts_with(&token));
+
+    let result = ping::new("::1".parse().unwrap())
+        .timeout(Duration::from_secs(1))
+        .payload_size(4000)
+        .send()
+        .unwrap();
+    assert_eq!(result.payload.len(), 4000);
+}
+
+#[test]
+fn payload_size_too_large() {
+    // Refused before any socket is opened, so no capability is needed.
+    for (addr, payload_size) in [
+        ("127.0.0.1", 65508),
+        ("::1", 65528),
+        ("127.0.0.1", usize::MAX),
+    ] {
+        let result = ping::new(addr.parse().unwrap())
+            .payload_size(payload_size)
+            .send();
+        match result {
+            Err(ping::Error::IoError { error }) => {
+                assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput)
+            }
+            result => panic!("expected an invalid payload size, got {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn payload_pattern() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let result = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .payload_size(64)
+        .payload_pattern(ping::PayloadPattern::Repeat(&[0xff, 0x00]))
+        .send()
+        .unwrap();
+    assert_eq!(&result.payload[24..28], &[0xff, 0x00, 0xff, 0x00]);
+    assert!(!result.corrupted);
+
+    let result = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .payload_size(64)
+        .payload_pattern(ping::PayloadPattern::Random)
+        .send()
+        .unwrap();
+    assert!(!result.corrupted);
+}
+
+#[cfg(not(target_os = "windows"))]
+fn checksum(buffer: &[u8]) -> u16 {
+    let mut sum = buffer
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum >> 16 > 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Sends a forged host unreachable to 127.0.0.1 after a short delay, quoting
+/// an echo request to 198.51.100.1, in case no router reports one.
+#[cfg(not(target_os = "windows"))]
+fn forge_unreachable(ident: u16, seq_cnt: u16, token: [u8; 24]) -> std::thread::JoinHandle<()> {
+    let mut quoted = vec![
+        0x45, 0, 0, 52, 0, 0, 0, 0, 64, 1, 0, 0, 127, 0, 0, 1, 198, 51, 100, 1,
+    ];
+    let ip_checksum = checksum(&quoted);
+    quoted[10..12].copy_from_slice(&ip_checksum.to_be_bytes());
+    let mut echo = vec![8, 0, 0, 0];
+    echo.extend_from_slice(&ident.to_be_bytes());
+    echo.extend_from_slice(&seq_cnt.to_be_bytes());
+    echo.extend_from_slice(&token);
+    let echo_checksum = checksum(&echo);
+    echo[2..4].copy_from_slice(&echo_checksum.to_be_bytes());
+    let mut message = vec![3, 1, 0, 0, 0, 0, 0, 0];
+    message.extend_from_slice(&quoted);
+    message.extend_from_slice(&echo);
+    let message_checksum = checksum(&message);
+    message[2..4].copy_from_slice(&message_checksum.to_be_bytes());
+
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).unwrap();
+        let loopback: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+        socket.send_to(&message, &loopback.into()).unwrap();
+    })
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn icmp_error() {
+    skip_if_not_root!();
+    let target = "198.51.100.1".parse().unwrap();
+    let token = [9; 24];
+
+    let forger = forge_unreachable(0x1092, 7, token);
+    let result = ping::new(target)
+        .socket_type(ping::RAW)
+        .timeout(Duration::from_secs(2))
+        .ident(0x1092)
+        .seq_cnt(7)
+        .payload(&token)
+        .send();
+    forger.join().unwrap();
+
+    // A router on the way may beat the forged message with a real one.
+    assert!(
+        matches!(result, Err(ping::Error::DestinationUnreachable { .. })),
+        "expected destination unreachable, got {result:?}"
+    );
+}
+
+/// Sends an ICMPv6 Destination Unreachable (code 4, port unreachable) about
+/// an echo request from ::1 to 2001:db8::1, quoted behind hop-by-hop and
+/// destination options extension headers, to ::1 after a short delay.
+#[cfg(not(target_os = "windows"))]
+fn forge_unreachable_v6(ident: u16, seq_cnt: u16, token: [u8; 24]) -> std::thread::JoinHandle<()> {
+    let source: std::net::Ipv6Addr = "::1".parse().unwrap();
+    let destination: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let mut quoted = vec![0x60, 0, 0, 0, 0, 8 + 8 + 32, 0, 1];
+    quoted.extend_from_slice(&source.octets());
+    quoted.extend_from_slice(&destination.octets());
+    // Hop-by-hop options, then destination options, each holding a PadN
+    // option to fill its 8 bytes.
+    quoted.extend_from_slice(&[60, 0, 1, 4, 0, 0, 0, 0]);
+    quoted.extend_from_slice(&[58, 0, 1, 4, 0, 0, 0, 0]);
+    quoted.extend_from_slice(&[128, 0, 0, 0]);
+    quoted.extend_from_slice(&ident.to_be_bytes());
+    quoted.extend_from_slice(&seq_cnt.to_be_bytes());
+    quoted.extend_from_slice(&token);
+    let mut message = vec![1, 4, 0, 0, 0, 0, 0, 0];
+    message.extend_from_slice(&quoted);
+
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        // The kernel fills in the ICMPv6 checksum.
+        let socket = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)).unwrap();
+        let loopback: std::net::SocketAddr = "[::1]:0".parse().unwrap();
+        socket.send_to(&message, &loopback.into()).unwrap();
+    })
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn icmp_error_v6() {
+    skip_if_not_root!();
+    let token = [3; 24];
+
+    let forger = forge_unreachable_v6(0x2093, 11, token);
+    // Nothing answers a hop limit of 1 towards the documentation prefix, so
+    // the forged message is the only answer.
+    let result = ping::new("2001:db8::1".parse().unwrap())
+        .socket_type(ping::RAW)
+        .timeout(Duration::from_secs(2))
+        .ttl(1)
+        .ident(0x2093)
+        .seq_cnt(11)
+        .payload(&token)
+        .send();
+    forger.join().unwrap();
+
+    match result {
+        Err(ping::Error::DestinationUnreachable { reporter, code }) => {
+            assert_eq!(reporter, "::1".parse::<std::net::IpAddr>().unwrap());
+            assert_eq!(code, 4);
+        }
+        result => panic!("expected destination unreachable, got {result:?}"),
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn icmp_error_dgram() {
+    skip_if_not_root!();
+    skip_if_no_capability!();
+    let token = [5; 24];
+    let mut pinger = ping::new("127.0.0.1".parse().unwrap())
+        .socket_type(ping::DGRAM)
+        .timeout(Duration::from_secs(2))
+        .payload(&token)
+        .pinger();
+    // The kernel routes ICMP errors to a datagram socket by its identifier.
+    let ident = pinger.send().unwrap().ident;
+
+    let forger = forge_unreachable(ident, pinger.seq_cnt(), token);
+    let results = pinger.send_many(&["198.51.100.1".parse().unwrap()]);
+    forger.join().unwrap();
+
+    assert!(
+        matches!(
+            results[..],
+            [Err(ping::Error::DestinationUnreachable { .. })]
+        ),
+        "expected destination unreachable, got {results:?}"
+    );
+}
+
+/// Sends an echo reply to 127.0.0.1, where a RAW socket waiting for the
+/// reply to the given request picks it up, with a wrong checksum unless
+/// `checksum_valid`.
+#[cfg(not(target_os = "windows"))]
+fn forge_reply(ident: u16, seq_cnt: u16, payload: &[u8], checksum_valid: bool) {
+    let mut reply = vec![0, 0, 0, 0];
+    reply.extend_from_slice(&ident.to_be_bytes());
+    reply.extend_from_slice(&seq_cnt.to_be_bytes());
+    reply.extend_from_slice(payload);
+    let mut reply_checksum = checksum(&reply);
+    if !checksum_valid {
+        reply_checksum ^= 0x0101;
+    }
+    reply[2..4].copy_from_slice(&reply_checksum.to_be_bytes());
+
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).unwrap();
+    let loopback: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+    socket.send_to(&reply, &loopback.into()).unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn bad_checksum() {
+    skip_if_not_root!();
+    let token = [7; 24];
+    for strict in [false, true] {
+        let mut pinger = ping::new("127.0.0.1".parse().unwrap())
+            .socket_type(ping::RAW)
+            .timeout(Duration::from_secs(1))
+            .ident(0x3094)
+            .payload(&token)
+            .strict_checksums(strict)
+            .pinger();
+        assert!(!pinger.send().unwrap().bad_checksum);
+
+        // Queued on the socket before the request is even sent, the damaged
+        // reply is the first one read.
+        forge_reply(0x3094, pinger.seq_cnt(), &token, false);
+        let result = pinger.send().unwrap();
+        assert_eq!(result.bad_checksum, !strict);
+
+        let mut stats = ping::PingStatistics::new();
+        stats.record(&result);
+        assert_eq!(stats.corrupted(), usize::from(!strict));
+    }
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn reply_ident() {
+    skip_if_not_root!();
+    let mut pinger = ping::new("127.0.0.1".parse().unwrap())
+        .socket_type(ping::RAW)
+        .timeout(Duration::from_secs(1))
+        .ident(0x3095)
+        .payload_size(0)
+        .pinger();
+    assert_eq!(pinger.send().unwrap().ident, 0x3095);
+
+    // A reply to another process with the same sequence number, queued
+    // first, carries no token that tells it apart.
+    forge_reply(0x3096, pinger.seq_cnt(), &[], true);
+    assert_eq!(pinger.send().unwrap().ident, 0x3095);
+}
+
+#[test]
+#[cfg(not(target_os = "windows"))]
+fn ip_options() {
+    skip_if_not_root!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let loopback = std::net::Ipv4Addr::LOCALHOST;
+
+    let result = ping::new(addr)
+        .socket_type(ping::RAW)
+        .timeout(Duration::from_secs(1))
+        .ip_option(ping::IpOption::RecordRoute)
+        .send()
+        .unwrap();
+    let route = result.route.unwrap();
+    assert!(!route.is_empty());
+    assert!(route.iter().all(|hop| *hop == loopback));
+    assert!(result.ip_timestamps.is_none());
+
+    let result = ping::new(addr)
+        .socket_type(ping::RAW)
+        .timeout(Duration::from_secs(1))
+        .ip_option(ping::IpOption::PrespecifiedTimestamps(&[loopback]))
+        .send()
+        .unwrap();
+    let timestamps = result.ip_timestamps.unwrap();
+    assert_eq!(timestamps.len(), 1);
+    assert_eq!(timestamps[0].addr, Some(loopback));
+    assert!(result.route.is_none());
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn reply_ttl() {
+    skip_if_no_capability!();
+    for addr in ["127.0.0.1", "::1"] {
+        let result = ping::new(addr.parse().unwrap())
+            .socket_type(ping::DGRAM)
+            .timeout(Duration::from_secs(1))
+            .send()
+            .unwrap();
+        assert!(result.ttl.is_some(), "no TTL reported for {addr}");
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn tos() {
+    skip_if_no_capability!();
+    for addr in ["127.0.0.1", "::1"] {
+        // AF41; loopback echoes the marking back.
+        let result = ping::new(addr.parse().unwrap())
+            .socket_type(ping::DGRAM)
+            .timeout(Duration::from_secs(1))
+            .tos(34 << 2)
+            .send()
+            .unwrap();
+        assert_eq!(result.dscp, Some(34), "{addr}");
+        assert_eq!(result.ecn, Some(0), "{addr}");
+    }
+}
+
+#[test]
+fn bind_addr() {
+    skip_if_no_capability!();
+    for addr in ["127.0.0.1", "::1"] {
+        let addr = addr.parse().unwrap();
+        let result = ping::new(addr)
+            .timeout(Duration::from_secs(1))
+            .bind_addr(addr)
+            .send()
+            .unwrap();
+        assert_eq!(result.local_addr, Some(addr));
+    }
+
+    let result = ping::new("127.0.0.1".parse().unwrap())
+        .bind_addr("::1".parse().unwrap())
+        .send();
+    match result {
+        Err(ping::Error::IoError { error }) => {
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput)
+        }
+        result => panic!("expected an address family mismatch, got {result:?}"),
+    }
+}
+
+/// A link-local address of this host, with the index and name of its
+/// interface, from `/proc/net/if_inet6`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn link_local_addr() -> Option<(std::net::Ipv6Addr, u32, String)> {
+    let table = std::fs::read_to_string("/proc/net/if_inet6").ok()?;
+    table.lines().find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let [addr, index, _, scope, _, name] = fields[..] else {
+            return None;
+        };
+        if scope != "20" {
+            return None;
+        }
+        let addr = u128::from_str_radix(addr, 16).ok()?;
+        let index = u32::from_str_radix(index, 16).ok()?;
+        Some((addr.into(), index, name.to_string()))
+    })
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn link_local() {
+    skip_if_no_capability!();
+    let Some((addr, index, name)) = link_local_addr() else {
+        eprintln!("Skipping test: no link-local IPv6 address");
+        return;
+    };
+    let timeout = Duration::from_secs(1);
+
+    let result = ping::Ping::new_scoped(std::net::SocketAddrV6::new(addr, 0, 0, index))
+        .timeout(timeout)
+        .send()
+        .unwrap();
+    assert_eq!(result.source, addr);
+
+    let result = ping::new(addr.into())
+        .timeout(timeout)
+        .scope_interface(&name)
+        .send()
+        .unwrap();
+    assert_eq!(result.source, addr);
+
+    let result = ping::new(addr.into())
+        .timeout(timeout)
+        .scope_id(index)
+        .bind_addr(addr.into())
+        .send()
+        .unwrap();
+    assert_eq!(result.local_addr, Some(addr.into()));
+
+    let result = ping::new(addr.into())
+        .timeout(timeout)
+        .scope_interface("no-such-if0")
+        .send();
+    assert!(result.is_err());
+}
+
+#[test]
+fn traceroute() {
+    skip_if_no_capability!();
+    for addr in ["127.0.0.1", "::1"] {
+        let addr = addr.parse().unwrap();
+        let hops: Vec<_> = ping::new(addr)
+            .timeout(Duration::from_secs(1))
+            .traceroute()
+            .probes_per_hop(2)
+            .collect();
+        assert_eq!(hops.len(), 1);
+        assert_eq!(hops[0].ttl, 1);
+        assert!(hops[0].reached());
+        assert_eq!(hops[0].probes.len(), 2);
+        for probe in &hops[0].probes {
+            assert_eq!(probe.as_ref().unwrap().addr, addr);
+        }
+    }
+}
+
+#[test]
+fn mtr() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let reports: Vec<_> = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .count(2)
+        .interval(Duration::from_millis(10))
+        .mtr()
+        .collect();
+    assert_eq!(reports.len(), 2);
+
+    let report = &reports[1];
+    assert_eq!(report.rounds, 2);
+    assert_eq!(report.hops.len(), 1);
+    assert_eq!(report.hops[0].ttl, 1);
+    assert_eq!(report.hops[0].statistics.received(), 2);
+    assert_eq!(report.hops[0].addrs, [(addr, 2)]);
+}
+
 #[test]
 fn ping_result_fields_v6() {
     skip_if_no_capability!();
@@ -228,3 +784,338 @@ fn ping_result_raw_socket() {
     // Verify payload exists
     assert!(result.payload.len() >= 24); // TOKEN_SIZE
 }
+
+#[cfg(all(feature = "tokio", unix))]
+#[tokio::test]
+async fn send_async() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let custom_seq = 7;
+
+    let result = ping::new(addr)
+        .timeout(timeout)
+        .seq_cnt(custom_seq)
+        .send_async()
+        .await
+        .unwrap();
+
+    assert!(result.rtt <= timeout);
+    assert_eq!(result.seq_cnt, custom_seq);
+    assert_eq!(result.source, addr);
+}
+
+#[cfg(feature = "async-io")]
+#[test]
+fn send_async_io() {
+    skip_if_no_capability!();
+    let addr = "::1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let custom_seq = 8;
+
+    let result = async_io::block_on(
+        ping::new(addr)
+            .timeout(timeout)
+            .seq_cnt(custom_seq)
+            .send_async_io(),
+    )
+    .unwrap();
+
+    assert!(result.rtt <= timeout);
+    assert_eq!(result.seq_cnt, custom_seq);
+    assert_eq!(result.source, addr);
+}
+
+#[test]
+fn pinger_session() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+    let mut pinger = ping::new(addr).timeout(timeout).seq_cnt(10).pinger();
+
+    let first = pinger.send().unwrap();
+    let second = pinger.send().unwrap();
+    let third = pinger.send().unwrap();
+
+    assert_eq!(first.seq_cnt, 10);
+    assert_eq!(second.seq_cnt, 11);
+    assert_eq!(third.seq_cnt, 12);
+    assert_eq!(pinger.seq_cnt(), 13);
+
+    // The socket is reused, so the identifier stays the same, even where the
+    // kernel assigns it.
+    assert_eq!(first.ident, second.ident);
+    assert_eq!(second.ident, third.ident);
+    assert_ne!(first.payload, second.payload);
+}
+
+#[test]
+fn pinger_send_many() {
+    skip_if_no_capability!();
+    let targets = [
+        "127.0.0.1".parse().unwrap(),
+        "::1".parse().unwrap(),
+        // TEST-NET-2, never answers.
+        "198.51.100.1".parse().unwrap(),
+    ];
+    let timeout = Duration::from_millis(500);
+    let mut pinger = ping::new(targets[0]).timeout(timeout).pinger();
+
+    let results = pinger.send_many(&targets);
+
+    assert_eq!(results.len(), targets.len());
+    let v4 = results[0].as_ref().unwrap();
+    let v6 = results[1].as_ref().unwrap();
+    assert_eq!(v4.source, targets[0]);
+    assert_eq!(v6.source, targets[1]);
+    assert_eq!(v4.seq_cnt, 1);
+    assert_eq!(v6.seq_cnt, 1);
+    assert!(results[2].is_err());
+    assert_eq!(pinger.seq_cnt(), 2);
+}
+
+#[test]
+fn iter_count() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let results: Vec<_> = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .seq_cnt(5)
+        .count(3)
+        .interval(Duration::from_millis(50))
+        .iter()
+        .collect();
+
+    let seq_cnts: Vec<_> = results
+        .iter()
+        .map(|r| r.as_ref().unwrap().seq_cnt)
+        .collect();
+    assert_eq!(seq_cnts, [5, 6, 7]);
+}
+
+#[test]
+fn iter_deadline() {
+    skip_if_no_capability!();
+    // TEST-NET-2, never answers: every request yields an error, yet the
+    // iteration only ends at the deadline.
+    let addr = "198.51.100.1".parse().unwrap();
+    let time_start = SystemTime::now();
+    let results: Vec<_> = ping::new(addr)
+        .timeout(Duration::from_millis(100))
+        .interval(Duration::from_millis(150))
+        .deadline(Duration::from_millis(500))
+        .iter()
+        .collect();
+
+    assert!(results.len() >= 3 && results.len() <= 4);
+    assert!(results.iter().all(|r| r.is_err()));
+    assert!(SystemTime::now().duration_since(time_start).unwrap() < Duration::from_secs(1));
+}
+
+#[cfg(all(feature = "tokio", unix))]
+#[tokio::test]
+async fn iter_next_async() {
+    skip_if_no_capability!();
+    let addr = "::1".parse().unwrap();
+    let mut iter = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .count(2)
+        .interval(Duration::from_millis(50))
+        .iter();
+
+    assert_eq!(iter.next_async().await.unwrap().unwrap().seq_cnt, 1);
+    assert_eq!(iter.next_async().await.unwrap().unwrap().seq_cnt, 2);
+    assert!(iter.next_async().await.is_none());
+}
+
+#[cfg(all(feature = "tokio", unix))]
+#[tokio::test]
+async fn iter_stream() {
+    use futures_lite::StreamExt;
+
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let results: Vec<_> = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .count(3)
+        .interval(Duration::from_millis(50))
+        .iter()
+        .into_stream()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+
+    let seq_cnts: Vec<_> = results.iter().map(|result| result.seq_cnt).collect();
+    assert_eq!(seq_cnts, [1, 2, 3]);
+    // The requests share one socket, and with it their identifier.
+    assert!(
+        results
+            .iter()
+            .all(|result| result.ident == results[0].ident)
+    );
+}
+
+#[test]
+fn statistics() {
+    let mut stats = ping::PingStatistics::new();
+    assert_eq!(stats.loss(), 0.0);
+    assert_eq!(stats.avg(), None);
+
+    stats.record_rtt(Duration::from_millis(10));
+    stats.record_rtt(Duration::from_millis(20));
+    stats.record_loss();
+    stats.record_rtt(Duration::from_millis(30));
+
+    assert_eq!(stats.transmitted(), 4);
+    assert_eq!(stats.received(), 3);
+    assert_eq!(stats.loss(), 25.0);
+    assert_eq!(stats.min(), Some(Duration::from_millis(10)));
+    assert_eq!(stats.avg(), Some(Duration::from_millis(20)));
+    assert_eq!(stats.max(), Some(Duration::from_millis(30)));
+    // sqrt(((10² + 20² + 30²) / 3) - 20²) ms
+    assert_eq!(stats.mdev(), Some(Duration::from_nanos(8_164_966)));
+    // 10/16 ms, then 0.625 + (10 - 0.625) / 16 ms
+    assert_eq!(stats.jitter(), Some(Duration::from_nanos(1_210_938)));
+    assert_eq!(
+        stats.to_string(),
+        "4 packets transmitted, 3 received, 25% packet loss\n\
+         rtt min/avg/max/mdev = 10.000/20.000/30.000/8.165 ms"
+    );
+}
+
+#[test]
+fn statistics_display_loss() {
+    let mut stats = ping::PingStatistics::new();
+    assert_eq!(
+        stats.to_string(),
+        "0 packets transmitted, 0 received, 0% packet loss"
+    );
+
+    stats.record_loss();
+    stats.record_rtt(Duration::from_millis(1));
+    stats.record_rtt(Duration::from_millis(1));
+    assert!(
+        stats
+            .to_string()
+            .starts_with("3 packets transmitted, 2 received, 33.3333% packet loss\n")
+    );
+
+    stats.record_loss();
+    stats.record_loss();
+    stats.record_loss();
+    assert!(stats.to_string().contains(", 66.6667% packet loss\n"));
+}
+
+/// A clock that moves `step` forward every time it is read.
+#[derive(Debug)]
+struct SteppingClock {
+    start: Instant,
+    step: Duration,
+    reads: AtomicU32,
+}
+
+impl SteppingClock {
+    fn new(step: Duration) -> Self {
+        SteppingClock {
+            start: Instant::now(),
+            step,
+            reads: AtomicU32::new(0),
+        }
+    }
+}
+
+impl ping::Clock for SteppingClock {
+    fn now(&self) -> Instant {
+        self.start + self.step * self.reads.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn fake_clock_rtt() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let clock = SteppingClock::new(Duration::from_millis(3));
+
+    let result = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .clock(&clock)
+        .send()
+        .unwrap();
+
+    // The clock is read once when sending and once when the reply arrives.
+    assert_eq!(result.rtt, Duration::from_millis(3));
+}
+
+#[test]
+fn fake_clock_timeout() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let clock = SteppingClock::new(Duration::from_secs(2));
+
+    // The reply arrives, but the clock says it took longer than the timeout.
+    let error = ping::new(addr)
+        .timeout(Duration::from_secs(1))
+        .clock(&clock)
+        .send()
+        .unwrap_err();
+
+    match error {
+        ping::Error::IoError { error } => assert_eq!(error.kind(), std::io::ErrorKind::TimedOut),
+        error => panic!("unexpected error: {error}"),
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn kernel_timestamps() {
+    skip_if_no_capability!();
+    let addr = "127.0.0.1".parse().unwrap();
+    let timeout = Duration::from_secs(1);
+
+    let result = ping::new(addr)
+        .timeout(timeout)
+        .kernel_timestamps(true)
+        .send()
+        .unwrap();
+
+    assert_ne!(result.timestamp_source, ping::TimestampSource::Clock);
+    assert!(result.rtt < timeout);
+
+    let result = ping::new(addr).timeout(timeout).send().unwrap();
+    assert_eq!(result.timestamp_source, ping::TimestampSource::Clock);
+}
+
+#[test]
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "freebsd"
+))]
+fn path_mtu() {
+    skip_if_no_capability!();
+    for addr in ["127.0.0.1", "::1"] {
+        let mtu = ping::new(addr.parse().unwrap())
+            .timeout(Duration::from_secs(1))
+            .path_mtu()
+            .unwrap();
+        assert!((1280..=65535).contains(&mtu), "{addr}: {mtu}");
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn path_mtu_ip_options() {
+    skip_if_not_root!();
+    let path_mtu = |ip_option| {
+        let mut ping = ping::new("127.0.0.1".parse().unwrap());
+        ping.socket_type(ping::RAW).timeout(Duration::from_secs(1));
+        if let Some(ip_option) = ip_option {
+            ping.ip_option(ip_option);
+        }
+        ping.path_mtu().unwrap()
+    };
+
+    // The option takes room from the payload, not from the MTU.
+    assert_eq!(path_mtu(Some(ping::IpOption::RecordRoute)), path_mtu(None));
+}